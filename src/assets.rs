@@ -1,6 +1,12 @@
+use bevy::ecs::system::SystemParam;
+use bevy::image::{ImageAddressMode, ImageSampler, ImageSamplerDescriptor};
 use bevy::prelude::*;
+use bevy::render::render_resource::TextureFormat;
 use bevy_asset_loader::prelude::*;
 use bevy_hanabi::prelude::*;
+use iyes_progress::prelude::*;
+
+use crate::chunks::WorldSeed;
 
 #[derive(Clone, Eq, PartialEq, Debug, Hash, Default, States)]
 pub enum MyStates {
@@ -78,19 +84,165 @@ pub struct GameAssets {
     pub fire: Handle<EffectAsset>,
     pub void: Handle<EffectAsset>,
     pub golden_pickup: Handle<EffectAsset>,
+    pub grass_impact: Handle<EffectAsset>,
+    pub stone_impact: Handle<EffectAsset>,
+    pub glass_shatter: Handle<EffectAsset>,
+}
+
+/// How many particles the heavy ambient effects (torch fire and the void) use
+#[derive(Resource, Reflect, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[reflect(Resource)]
+pub enum ParticleQuality {
+    Low,
+    Medium,
+    #[default]
+    High,
+}
+
+impl ParticleQuality {
+    /// Multiplier on the spawn rate and capacity of the effects
+    pub fn density(self) -> f32 {
+        match self {
+            ParticleQuality::Low => 0.25,
+            ParticleQuality::Medium => 0.5,
+            ParticleQuality::High => 1.0,
+        }
+    }
+}
+
+/// The floating specks of the void background, some players find them disorienting
+#[derive(Resource, Reflect, Debug, Clone, Copy)]
+#[reflect(Resource)]
+pub struct VoidParticles {
+    pub enabled: bool,
+    /// Multiplier on top of the particle quality, between 0 and 1
+    pub density: f32,
+}
+
+impl Default for VoidParticles {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            density: 1.0,
+        }
+    }
+}
+
+/// Swirling wind that pushes the fire and void particles around
+#[derive(Resource, Reflect, Debug, Clone, Copy)]
+#[reflect(Resource)]
+pub struct Wind {
+    /// Peak acceleration of the wind, 0 for still air
+    pub strength: f32,
+}
+
+impl Default for Wind {
+    fn default() -> Self {
+        Self { strength: 0.6 }
+    }
+}
+
+/// Filtering of the repeating textures, which are stretched over large surfaces and shimmer in
+/// the distance without it
+#[derive(Resource, Reflect, Debug, Clone, Copy)]
+#[reflect(Resource)]
+pub struct TextureFiltering {
+    /// Anisotropic filtering level, 1 turns it off. Rounded up to a power of two, at most 16.
+    pub anisotropy: u16,
+}
+
+impl Default for TextureFiltering {
+    fn default() -> Self {
+        Self { anisotropy: 8 }
+    }
+}
+
+/// Seed the one-off particle effects from the world seed and a running count instead of the
+/// clock, so recordings and tests look the same every time
+#[derive(Resource, Reflect, Default)]
+#[reflect(Resource)]
+pub struct DeterministicParticles {
+    pub enabled: bool,
+    /// Effects seeded so far
+    #[reflect(ignore)]
+    spawned: u32,
+}
+
+/// Hands out the `prng_seed` for particle effects, see [`DeterministicParticles`]
+#[derive(SystemParam)]
+pub struct ParticleSeeds<'w> {
+    deterministic: ResMut<'w, DeterministicParticles>,
+    world_seed: Res<'w, WorldSeed>,
+    time: Res<'w, Time>,
 }
 
+impl ParticleSeeds<'_> {
+    pub fn next(&mut self) -> u32 {
+        if !self.deterministic.enabled {
+            return self.time.elapsed().as_micros() as u32;
+        }
+
+        self.deterministic.spawned = self.deterministic.spawned.wrapping_add(1);
+        // Spread consecutive counts over all bits, so neighbouring seeds differ a lot
+        self.world_seed.0 ^ self.deterministic.spawned.wrapping_mul(0x9E37_79B9)
+    }
+}
+
+/// Marks the void background effect so it can be hidden
+#[derive(Component)]
+pub struct VoidEffect;
+
 pub struct AssetPlugin;
 
 impl Plugin for AssetPlugin {
     fn build(&self, app: &mut App) {
+        // The progress plugin moves on once everything is loaded, and reports how far along
+        // loading is to the loading screen
         app.init_state::<MyStates>()
+            .add_plugins(
+                ProgressPlugin::<MyStates>::new()
+                    .with_state_transition(MyStates::AssetLoading, MyStates::AssetPreparing),
+            )
             .add_loading_state(
-                LoadingState::new(MyStates::AssetLoading)
-                    .continue_to_state(MyStates::AssetPreparing)
-                    .load_collection::<GameAssets>(),
+                LoadingState::new(MyStates::AssetLoading).load_collection::<GameAssets>(),
+            )
+            .add_systems(
+                OnEnter(MyStates::AssetPreparing),
+                (prepare_assets, apply_texture_filtering),
             )
-            .add_systems(OnEnter(MyStates::AssetPreparing), prepare_assets);
+            .add_systems(
+                Update,
+                apply_texture_filtering.run_if(
+                    in_state(MyStates::Next)
+                        .and(resource_changed::<TextureFiltering>)
+                        .and(not(resource_added::<TextureFiltering>)),
+                ),
+            )
+            .add_systems(
+                Update,
+                rebuild_effects_on_quality_change
+                    .run_if(in_state(MyStates::Next).and(particle_settings_changed)),
+            )
+            .add_systems(
+                Update,
+                toggle_void_effect
+                    .run_if(in_state(MyStates::Next).and(resource_changed::<VoidParticles>)),
+            );
+        app.register_type::<ParticleQuality>();
+        app.register_type::<VoidParticles>();
+        app.init_resource::<VoidParticles>();
+        app.register_type::<Wind>();
+        app.init_resource::<Wind>();
+        app.register_type::<TextureFiltering>();
+        app.init_resource::<TextureFiltering>();
+        app.register_type::<DeterministicParticles>();
+        app.init_resource::<DeterministicParticles>();
+        // The web build is usually running on weaker hardware
+        app.insert_resource(if cfg!(target_arch = "wasm32") {
+            ParticleQuality::Medium
+        } else {
+            ParticleQuality::High
+        });
     }
 }
 
@@ -98,16 +250,220 @@ fn prepare_assets(
     mut assets: ResMut<GameAssets>,
     mut effects: ResMut<Assets<EffectAsset>>,
     mut state: ResMut<NextState<MyStates>>,
+    quality: Res<ParticleQuality>,
+    void: Res<VoidParticles>,
+    wind: Res<Wind>,
 ) {
-    assets.fire = create_fire_effect(&mut effects);
-    assets.void = create_void_effect(&mut effects);
+    assets.fire = create_fire_effect(&mut effects, *quality, *wind);
+    assets.void = create_void_effect(&mut effects, *quality, *void, *wind);
     assets.golden_pickup = create_golden_pickup_effect(&mut effects);
+    assets.grass_impact = create_impact_effect(
+        &mut effects,
+        "grass_impact",
+        Vec4::new(0.45, 0.65, 0.25, 0.8),
+    );
+    assets.stone_impact = create_impact_effect(
+        &mut effects,
+        "stone_impact",
+        Vec4::new(0.55, 0.53, 0.5, 0.8),
+    );
+    assets.glass_shatter = create_impact_effect(
+        &mut effects,
+        "glass_shatter",
+        Vec4::new(0.85, 0.95, 1.0, 0.9),
+    );
 
     state.set(MyStates::Next);
 }
 
+/// Give the repeating textures mipmaps and set their anisotropic filtering
+fn apply_texture_filtering(
+    filtering: Res<TextureFiltering>,
+    assets: Res<GameAssets>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    let anisotropy = filtering.anisotropy.clamp(1, 16).next_power_of_two();
+
+    for handle in [
+        &assets.stones,
+        &assets.mossy_stones,
+        &assets.outside_grass,
+        &assets.lava,
+    ] {
+        let Some(image) = images.get_mut(handle) else {
+            continue;
+        };
+
+        if image.texture_descriptor.mip_level_count == 1 {
+            generate_mipmaps(image);
+        }
+
+        image.sampler = ImageSampler::Descriptor(ImageSamplerDescriptor {
+            address_mode_u: ImageAddressMode::Repeat,
+            address_mode_v: ImageAddressMode::Repeat,
+            address_mode_w: ImageAddressMode::Repeat,
+            anisotropy_clamp: anisotropy,
+            ..ImageSamplerDescriptor::linear()
+        });
+    }
+}
+
+fn srgb_to_linear(value: u8) -> f32 {
+    let value = value as f32 / 255.0;
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(value: f32) -> u8 {
+    let value = if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    };
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+/// Append a box filtered mip chain to an 8-bit RGBA image, the format png and jpg load as
+fn generate_mipmaps(image: &mut Image) {
+    let srgb = match image.texture_descriptor.format {
+        TextureFormat::Rgba8UnormSrgb => true,
+        TextureFormat::Rgba8Unorm => false,
+        _ => return,
+    };
+    // Averaging the sRGB encoded bytes directly would darken every level, so the colors are
+    // filtered in linear space. Alpha is stored linearly either way.
+    let to_linear: [f32; 256] = std::array::from_fn(|value| {
+        if srgb {
+            srgb_to_linear(value as u8)
+        } else {
+            value as f32 / 255.0
+        }
+    });
+
+    let (mut width, mut height) = (image.width() as usize, image.height() as usize);
+    let Some(data) = image.data.as_mut() else {
+        return;
+    };
+
+    let mut level = data.clone();
+    let mut level_count = 1;
+    while width > 1 || height > 1 {
+        let (next_width, next_height) = ((width / 2).max(1), (height / 2).max(1));
+        let mut next = Vec::with_capacity(next_width * next_height * 4);
+
+        for y in 0..next_height {
+            for x in 0..next_width {
+                for channel in 0..4 {
+                    // Clamped at the edge, for odd sizes
+                    let samples = [(0, 0), (1, 0), (0, 1), (1, 1)].map(|(dx, dy)| {
+                        let sx = (2 * x + dx).min(width - 1);
+                        let sy = (2 * y + dy).min(height - 1);
+                        level[(sy * width + sx) * 4 + channel]
+                    });
+
+                    if channel < 3 {
+                        let sum: f32 = samples.iter().map(|&s| to_linear[s as usize]).sum();
+                        next.push(if srgb {
+                            linear_to_srgb(sum / 4.0)
+                        } else {
+                            (sum / 4.0 * 255.0).round() as u8
+                        });
+                    } else {
+                        let sum: u32 = samples.iter().map(|&s| s as u32).sum();
+                        next.push(((sum + 2) / 4) as u8);
+                    }
+                }
+            }
+        }
+
+        data.extend_from_slice(&next);
+        level = next;
+        (width, height) = (next_width, next_height);
+        level_count += 1;
+    }
+
+    image.texture_descriptor.mip_level_count = level_count;
+}
+
+fn particle_settings_changed(
+    quality: Res<ParticleQuality>,
+    void: Res<VoidParticles>,
+    wind: Res<Wind>,
+) -> bool {
+    (quality.is_changed() && !quality.is_added())
+        || (void.is_changed() && !void.is_added())
+        || (wind.is_changed() && !wind.is_added())
+}
+
+/// Recreate the quality dependent effects and point the existing instances at them
+fn rebuild_effects_on_quality_change(
+    quality: Res<ParticleQuality>,
+    void: Res<VoidParticles>,
+    wind: Res<Wind>,
+    mut assets: ResMut<GameAssets>,
+    mut effects: ResMut<Assets<EffectAsset>>,
+    mut instances: Query<&mut ParticleEffect>,
+) {
+    let old_fire = std::mem::replace(
+        &mut assets.fire,
+        create_fire_effect(&mut effects, *quality, *wind),
+    );
+    let old_void = std::mem::replace(
+        &mut assets.void,
+        create_void_effect(&mut effects, *quality, *void, *wind),
+    );
+
+    for mut instance in instances.iter_mut() {
+        if instance.handle == old_fire {
+            instance.handle = assets.fire.clone();
+        } else if instance.handle == old_void {
+            instance.handle = assets.void.clone();
+        }
+    }
+}
+
+fn toggle_void_effect(
+    void: Res<VoidParticles>,
+    mut effects: Query<&mut Visibility, With<VoidEffect>>,
+) {
+    for mut visibility in effects.iter_mut() {
+        *visibility = if void.enabled {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+    }
+}
+
+/// Acceleration of a swirling wind field, a cheap stand-in for curl noise. Each axis is pushed
+/// by a wave travelling along another axis, so nearby particles curl around each other.
+/// `frequency` is the number of swirls per meter.
+fn wind_accel(writer: &ExprWriter, strength: f32, frequency: f32) -> WriterExpr {
+    let pos = writer.attr(Attribute::POSITION).mul(writer.lit(frequency));
+    let swirl = |along: WriterExpr, across: WriterExpr, scale: f32| {
+        along
+            .add(writer.time())
+            .sin()
+            .mul(across.cos())
+            .mul(writer.lit(strength * scale))
+    };
+
+    let x = swirl(pos.clone().y(), pos.clone().z(), 1.0);
+    // Mostly horizontal, like real wind
+    let y = swirl(pos.clone().z(), pos.clone().x(), 0.3);
+    let z = swirl(pos.clone().x(), pos.y(), 1.0);
+    x.vec3(y, z)
+}
+
 /// Create a fire particle effect
-fn create_fire_effect(effects: &mut ResMut<Assets<EffectAsset>>) -> Handle<EffectAsset> {
+fn create_fire_effect(
+    effects: &mut ResMut<Assets<EffectAsset>>,
+    quality: ParticleQuality,
+    wind: Wind,
+) -> Handle<EffectAsset> {
     // More realistic fire color gradient:
     // - White/yellow hot core at base (intense heat)
     // - Orange/yellow in the middle (main flame)
@@ -158,8 +514,11 @@ fn create_fire_effect(effects: &mut ResMut<Assets<EffectAsset>>) -> Handle<Effec
     let velocity = random_x.vec3(upward_speed, random_z);
     let init_vel = SetAttributeModifier::new(Attribute::VELOCITY, velocity.expr());
 
-    // Add upward acceleration (buoyancy) - hot air accelerates upward
-    let accel = writer.lit(Vec3::new(0.0, 2.3, 0.0)).expr();
+    // Add upward acceleration (buoyancy) - hot air accelerates upward, and flickers in the wind
+    let accel = writer
+        .lit(Vec3::new(0.0, 2.3, 0.0))
+        .add(wind_accel(&writer, wind.strength, 4.0))
+        .expr();
     let update_accel = AccelModifier::new(accel);
 
     // Add drag to simulate air resistance (less drag = particles rise higher)
@@ -168,8 +527,9 @@ fn create_fire_effect(effects: &mut ResMut<Assets<EffectAsset>>) -> Handle<Effec
 
     effects.add(
         EffectAsset::new(
-            32768,
-            SpawnerSettings::rate(80.0.into()), // Higher spawn rate for denser fire
+            (32768.0 * quality.density()) as u32,
+            // Higher spawn rate for denser fire
+            SpawnerSettings::rate((80.0 * quality.density()).into()),
             writer.finish(),
         )
         .with_name("fire")
@@ -177,7 +537,7 @@ fn create_fire_effect(effects: &mut ResMut<Assets<EffectAsset>>) -> Handle<Effec
         .init(init_vel)
         .init(init_age)
         .init(init_lifetime)
-        .update(update_accel) // Buoyancy
+        .update(update_accel) // Buoyancy and wind
         .update(update_drag) // Air resistance
         .render(ColorOverLifetimeModifier {
             gradient: color_gradient,
@@ -196,7 +556,14 @@ fn create_fire_effect(effects: &mut ResMut<Assets<EffectAsset>>) -> Handle<Effec
 }
 
 /// Create a void-like background particle effect with slow-moving white particles
-fn create_void_effect(effects: &mut ResMut<Assets<EffectAsset>>) -> Handle<EffectAsset> {
+fn create_void_effect(
+    effects: &mut ResMut<Assets<EffectAsset>>,
+    quality: ParticleQuality,
+    void: VoidParticles,
+    wind: Wind,
+) -> Handle<EffectAsset> {
+    let density = quality.density() * void.density.clamp(0.0, 1.0);
+
     let mut color_gradient = bevy_hanabi::Gradient::new();
     color_gradient.add_key(0.0, Vec4::new(1.0, 1.0, 1.0, 0.0)); // Start invisible (fade in)
     color_gradient.add_key(0.1, Vec4::new(1.0, 1.0, 1.0, 0.15)); // Fade in quickly
@@ -236,10 +603,15 @@ fn create_void_effect(effects: &mut ResMut<Assets<EffectAsset>>) -> Handle<Effec
     let drag = writer.lit(0.1).expr();
     let update_drag = LinearDragModifier::new(drag);
 
+    // Wide, slow swirls across the whole background
+    let update_wind = AccelModifier::new(wind_accel(&writer, wind.strength, 0.05).expr());
+
     effects.add(
         EffectAsset::new(
-            32768,                              // Increased particle capacity for more particles
-            SpawnerSettings::rate(60.0.into()), // Higher spawn rate for more particles
+            // Increased particle capacity and spawn rate for more particles
+            // At least one particle, an effect without capacity can't be created
+            ((32768.0 * density) as u32).max(1),
+            SpawnerSettings::rate((60.0 * density).into()),
             writer.finish(),
         )
         .with_name("void")
@@ -247,6 +619,7 @@ fn create_void_effect(effects: &mut ResMut<Assets<EffectAsset>>) -> Handle<Effec
         .init(init_vel)
         .init(init_age)
         .init(init_lifetime)
+        .update(update_wind)
         .update(update_drag)
         .render(ColorOverLifetimeModifier {
             gradient: color_gradient,
@@ -339,3 +712,124 @@ fn create_golden_pickup_effect(effects: &mut ResMut<Assets<EffectAsset>>) -> Han
         }),
     )
 }
+
+/// Name of the property scaling how far impact particles fly, set per burst from the
+/// impact speed
+pub const IMPACT_STRENGTH_PROPERTY: &str = "strength";
+
+/// Create a burst of dust kicked up from the ground, tinted like the surface that was hit
+fn create_impact_effect(
+    effects: &mut ResMut<Assets<EffectAsset>>,
+    name: &str,
+    color: Vec4,
+) -> Handle<EffectAsset> {
+    // Surface colored motes that fade out while drifting down
+    let mut color_gradient = bevy_hanabi::Gradient::new();
+    color_gradient.add_key(0.0, color);
+    color_gradient.add_key(0.6, color.with_w(color.w * 0.5));
+    color_gradient.add_key(1.0, color.with_w(0.0));
+
+    let mut size_gradient = bevy_hanabi::Gradient::new();
+    size_gradient.add_key(0.0, Vec3::splat(0.04));
+    size_gradient.add_key(0.3, Vec3::splat(0.06)); // Dust puffs up
+    size_gradient.add_key(1.0, Vec3::splat(0.02));
+
+    let writer = ExprWriter::new();
+
+    let strength = writer.add_property(IMPACT_STRENGTH_PROPERTY, 1.0_f32.into());
+
+    let age = writer.lit(0.).expr();
+    let init_age = SetAttributeModifier::new(Attribute::AGE, age);
+
+    let lifetime = writer.lit(0.4).uniform(writer.lit(0.9)).expr();
+    let init_lifetime = SetAttributeModifier::new(Attribute::LIFETIME, lifetime);
+
+    // Spawn in a flat ring around the point of impact
+    let init_pos = SetPositionCircleModifier {
+        center: writer.lit(Vec3::ZERO).expr(),
+        axis: writer.lit(Vec3::Y).expr(),
+        radius: writer.lit(0.15).expr(),
+        dimension: ShapeDimension::Volume,
+    };
+
+    // Mostly sideways with a little hop, harder impacts throw the dust further
+    let random_x = writer.lit(-1.5).uniform(writer.lit(1.5));
+    let random_y = writer.lit(0.5).uniform(writer.lit(1.5));
+    let random_z = writer.lit(-1.5).uniform(writer.lit(1.5));
+    let velocity = random_x.vec3(random_y, random_z).mul(writer.prop(strength));
+    let init_vel = SetAttributeModifier::new(Attribute::VELOCITY, velocity.expr());
+
+    let accel = writer.lit(Vec3::new(0.0, -3.0, 0.0)).expr();
+    let update_accel = AccelModifier::new(accel);
+
+    let drag = writer.lit(3.0).expr();
+    let update_drag = LinearDragModifier::new(drag);
+
+    effects.add(
+        EffectAsset::new(256, SpawnerSettings::once(24.0.into()), writer.finish())
+            .with_name(name.to_string())
+            .init(init_pos)
+            .init(init_vel)
+            .init(init_age)
+            .init(init_lifetime)
+            .update(update_accel) // Gravity
+            .update(update_drag) // Air resistance
+            .render(ColorOverLifetimeModifier {
+                gradient: color_gradient,
+                blend: ColorBlendMode::Modulate,
+                mask: ColorBlendMask::RGBA,
+            })
+            .render(SizeOverLifetimeModifier {
+                gradient: size_gradient,
+                screen_space_size: false,
+            })
+            .render(OrientModifier {
+                mode: OrientMode::FaceCameraPosition,
+                rotation: None,
+            }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::asset::RenderAssetUsages;
+    use bevy::render::render_resource::{Extent3d, TextureDimension};
+
+    #[test]
+    fn test_generate_mipmaps_odd_size() {
+        const WIDTH: usize = 5;
+        const HEIGHT: usize = 3;
+
+        // Red in every even column, black in the odd ones, all opaque
+        let mut pixels = Vec::with_capacity(WIDTH * HEIGHT * 4);
+        for _ in 0..HEIGHT {
+            for x in 0..WIDTH {
+                let red = if x % 2 == 0 { 255 } else { 0 };
+                pixels.extend_from_slice(&[red, 0, 0, 255]);
+            }
+        }
+
+        let mut image = Image::new(
+            Extent3d {
+                width: WIDTH as u32,
+                height: HEIGHT as u32,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            pixels,
+            TextureFormat::Rgba8UnormSrgb,
+            RenderAssetUsages::default(),
+        );
+        generate_mipmaps(&mut image);
+
+        // 5x3, 2x1 and 1x1
+        assert_eq!(image.texture_descriptor.mip_level_count, 3);
+        let data = image.data.as_ref().unwrap();
+        assert_eq!(data.len(), (5 * 3 + 2 + 1) * 4);
+
+        // Half red in linear space is much brighter than half of the sRGB byte (127)
+        let first_texel = &data[WIDTH * HEIGHT * 4..][..4];
+        assert_eq!(first_texel, &[188, 0, 0, 255]);
+    }
+}
@@ -1,6 +1,107 @@
 use avian3d::prelude::*;
+use bevy::input::mouse::{AccumulatedMouseScroll, MouseScrollUnit};
+use bevy::post_process::motion_blur::MotionBlur;
 use bevy::prelude::*;
-use bevy::window::CursorOptions;
+use bevy::window::{CursorOptions, WindowFocused};
+
+use crate::focus::real_time_compensation;
+use crate::player::controller::view_blockers;
+
+/// How the camera deals with geometry between it and the player
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Reflect)]
+pub enum CameraOcclusionMode {
+    /// Pull the camera in front of whatever is in the way
+    #[default]
+    PushIn,
+    /// Keep the camera where it is and fade the player out instead
+    FadePlayer,
+    /// Ignore occlusion entirely
+    None,
+}
+
+/// User tweakable motion blur, which scales with how fast the player moves
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct MotionBlurSettings {
+    pub enabled: bool,
+    /// Shutter angle at full speed, lower it to reduce the blur
+    pub max_shutter_angle: f32,
+}
+
+impl Default for MotionBlurSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_shutter_angle: 1.25,
+        }
+    }
+}
+
+/// How eagerly the camera chases the player
+#[derive(Resource, Reflect, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[reflect(Resource)]
+pub enum CameraFeel {
+    /// Follows and turns almost instantly
+    #[default]
+    Responsive,
+    /// Trails behind the player with noticeable lag
+    Cinematic,
+}
+
+impl CameraFeel {
+    /// The follow, rotation and distance smoothing speeds of the preset
+    fn smoothing(self) -> (f32, f32, f32) {
+        match self {
+            CameraFeel::Responsive => (12.0, 90.0, 6.0),
+            CameraFeel::Cinematic => (4.0, 8.0, 2.5),
+        }
+    }
+}
+
+/// What happens to the captured mouse when the window loses and regains focus
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct CursorLockSettings {
+    /// Capture the mouse again when returning to the window, if it was captured when leaving
+    pub relock_on_focus: bool,
+}
+
+impl Default for CursorLockSettings {
+    fn default() -> Self {
+        Self {
+            relock_on_focus: true,
+        }
+    }
+}
+
+/// Detached fly camera for screenshots and looking around the world, toggled in debug builds
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct FreeCamera {
+    pub active: bool,
+    /// Flying speed in meters per second, doubled while holding shift
+    pub speed: f32,
+}
+
+impl Default for FreeCamera {
+    fn default() -> Self {
+        Self {
+            active: false,
+            speed: 10.0,
+        }
+    }
+}
+
+/// Run condition for everything that moves the player or follows it with the camera
+pub fn free_camera_inactive(free_camera: Res<FreeCamera>) -> bool {
+    !free_camera.active
+}
+
+#[cfg(debug_assertions)]
+pub const FREE_CAMERA_KEY: KeyCode = KeyCode::F6;
+
+/// Player speed at which the motion blur is the strongest (a fast fall or sprint)
+const FULL_BLUR_SPEED: f32 = 10.0;
 
 /// Component for third-person camera controller
 #[derive(Component)]
@@ -9,6 +110,10 @@ pub struct ThirdPersonCamera {
     pub yaw: f32,
     /// Vertical rotation (pitch) in radians
     pub pitch: f32,
+    /// Distance from the player picked with the mouse wheel, before zooming out with speed
+    pub zoom_distance: f32,
+    /// Change of the zoom distance per scroll wheel line
+    pub zoom_step: f32,
     /// Target distance from player
     pub target_distance: f32,
     /// Current smoothed distance from player
@@ -35,8 +140,8 @@ pub struct ThirdPersonCamera {
     pub max_pitch: f32,
     /// Collision detection radius
     pub collision_radius: f32,
-    /// Whether to enable collision detection
-    pub enable_collision: bool,
+    /// How to handle geometry blocking the view of the player
+    pub occlusion_mode: CameraOcclusionMode,
 }
 
 impl Default for ThirdPersonCamera {
@@ -44,6 +149,8 @@ impl Default for ThirdPersonCamera {
         Self {
             yaw: 0.0,
             pitch: -0.5, // Look slightly down
+            zoom_distance: 3.5,
+            zoom_step: 0.5,
             target_distance: 3.5,
             current_distance: 3.5,
             height_offset: 2.0,
@@ -57,7 +164,7 @@ impl Default for ThirdPersonCamera {
             min_pitch: -std::f32::consts::FRAC_PI_2 + 0.15,
             max_pitch: std::f32::consts::FRAC_PI_2 - 0.15,
             collision_radius: 0.3,
-            enable_collision: true,
+            occlusion_mode: CameraOcclusionMode::default(),
         }
     }
 }
@@ -67,8 +174,12 @@ pub fn handle_mouse_look(
     mut cursor_options: Single<&mut CursorOptions>,
     mut camera_query: Query<&mut ThirdPersonCamera>,
     mut cursor_events: MessageReader<bevy::input::mouse::MouseMotion>,
+    mut focus_events: MessageReader<WindowFocused>,
+    scroll: Res<AccumulatedMouseScroll>,
     keyboard: Res<ButtonInput<KeyCode>>,
     mouse: Res<ButtonInput<MouseButton>>,
+    settings: Res<CursorLockSettings>,
+    mut relock: Local<bool>,
 ) {
     let Ok(mut camera) = camera_query.single_mut() else {
         return;
@@ -80,6 +191,23 @@ pub fn handle_mouse_look(
         delta += event.delta;
     }
 
+    // Release the mouse when alt-tabbing away, and take it back on return
+    for focus in focus_events.read() {
+        if focus.focused {
+            if std::mem::take(&mut *relock) && settings.relock_on_focus {
+                cursor_options.grab_mode = bevy::window::CursorGrabMode::Locked;
+                cursor_options.visible = false;
+            }
+        } else {
+            *relock = cursor_options.grab_mode == bevy::window::CursorGrabMode::Locked;
+            cursor_options.grab_mode = bevy::window::CursorGrabMode::None;
+            cursor_options.visible = true;
+        }
+
+        // Motion piled up around the focus change would snap the camera around
+        delta = Vec2::ZERO;
+    }
+
     // Lock cursor for better camera control
     if mouse.just_pressed(MouseButton::Left) && !keyboard.pressed(KeyCode::ControlRight) {
         cursor_options.grab_mode = bevy::window::CursorGrabMode::Locked;
@@ -98,6 +226,14 @@ pub fn handle_mouse_look(
 
         // Clamp pitch to prevent flipping
         camera.pitch = camera.pitch.clamp(camera.min_pitch, camera.max_pitch);
+
+        // Scrolling up zooms in, pixel deltas (touchpads) are roughly a line per 100 pixels
+        let lines = match scroll.unit {
+            MouseScrollUnit::Line => scroll.delta.y,
+            MouseScrollUnit::Pixel => scroll.delta.y / 100.0,
+        };
+        camera.zoom_distance = (camera.zoom_distance - lines * camera.zoom_step)
+            .clamp(camera.min_distance, camera.max_distance);
     }
 }
 
@@ -112,7 +248,9 @@ pub fn update_camera_position(
             Without<ThirdPersonCamera>,
         ),
     >,
+    spatial_query: SpatialQuery,
     time: Res<Time>,
+    virtual_time: Res<Time<Virtual>>,
 ) {
     let Ok((mut camera_transform, mut camera)) = camera_query.single_mut() else {
         return;
@@ -122,16 +260,18 @@ pub fn update_camera_position(
         return;
     };
 
-    let delta_time = time.delta_secs();
+    // Smooth in real time, so the camera keeps up with the player while focus slows the world
+    let compensation = real_time_compensation(&virtual_time);
+    let delta_time = time.delta_secs() * compensation;
 
-    // Calculate player position and velocity
+    // Calculate player position and velocity, the latter in real time as well
     let player_pos = player_transform.translation;
-    let player_vel = player_velocity.0;
+    let player_vel = player_velocity.0 / compensation;
     let player_speed = player_vel.length();
 
     // Adjust target distance based on player speed (zoom out slightly when moving fast)
     // This creates a dynamic feel similar to Elden Ring
-    let base_distance = 3.5;
+    let base_distance = camera.zoom_distance;
     let speed_factor = (player_speed * 0.25).min(1.0);
     let dynamic_distance = base_distance + speed_factor * 0.3;
     camera.target_distance = dynamic_distance.clamp(camera.min_distance, camera.max_distance);
@@ -158,7 +298,7 @@ pub fn update_camera_position(
     let predicted_player_pos = player_pos + player_vel * velocity_prediction_factor;
     let desired_camera_pos = predicted_player_pos + camera_offset;
 
-    // For now, use desired position (collision detection can be added later with RayCaster component)
+    // Occlusion is resolved after smoothing, so the camera can't lag behind into a wall
     let final_camera_pos = desired_camera_pos;
 
     // Smooth camera position interpolation (spring-like behavior)
@@ -198,11 +338,22 @@ pub fn update_camera_position(
         current_pos.z.lerp(target_pos.z, horizontal_smoothing),
     );
 
-    camera_transform.translation = smoothed_pos;
-
     // Calculate look target (slightly above player center for better framing)
     let look_target = player_pos + Vec3::Y * 1.2;
 
+    let smoothed_pos = if camera.occlusion_mode == CameraOcclusionMode::PushIn {
+        push_in_front_of_occluders(
+            &spatial_query,
+            look_target,
+            smoothed_pos,
+            camera.collision_radius,
+        )
+    } else {
+        smoothed_pos
+    };
+
+    camera_transform.translation = smoothed_pos;
+
     // Very subtle rotation smoothing - fast enough to feel instant but smooths micro-jitters
     let target_rotation = Transform::from_translation(smoothed_pos)
         .looking_at(look_target, Vec3::Y)
@@ -214,3 +365,204 @@ pub fn update_camera_position(
         .rotation
         .slerp(target_rotation, rotation_smoothing_factor);
 }
+
+/// Move `camera_pos` towards `pivot` until a sphere of `radius` fits there without touching
+/// anything but the player
+fn push_in_front_of_occluders(
+    spatial_query: &SpatialQuery,
+    pivot: Vec3,
+    camera_pos: Vec3,
+    radius: f32,
+) -> Vec3 {
+    let offset = camera_pos - pivot;
+    let Ok(direction) = Dir3::new(offset) else {
+        return camera_pos;
+    };
+
+    let config = ShapeCastConfig {
+        max_distance: offset.length(),
+        // The player may stand right against a wall, that shouldn't collapse the camera
+        ignore_origin_penetration: true,
+        ..default()
+    };
+
+    match spatial_query.cast_shape(
+        &Collider::sphere(radius),
+        pivot,
+        Quat::IDENTITY,
+        direction,
+        &config,
+        &SpatialQueryFilter::from_mask(view_blockers()),
+    ) {
+        Some(hit) => pivot + direction * hit.distance,
+        None => camera_pos,
+    }
+}
+
+/// Fade the player model out while it is hidden behind something or the camera is right on
+/// top of it, for [`CameraOcclusionMode::FadePlayer`]
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
+pub fn fade_occluded_player(
+    camera_query: Query<(&Transform, &ThirdPersonCamera)>,
+    player_query: Query<
+        (Entity, &Transform),
+        (
+            With<bevy_tnua::prelude::TnuaController>,
+            Without<ThirdPersonCamera>,
+        ),
+    >,
+    children: Query<&Children>,
+    mesh_materials: Query<&MeshMaterial3d<StandardMaterial>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    spatial_query: SpatialQuery,
+    time: Res<Time>,
+    mut alpha: Local<Option<f32>>,
+) {
+    const FADED_ALPHA: f32 = 0.25;
+    const FADE_DISTANCE: f32 = 1.0;
+    const FADE_SPEED: f32 = 8.0;
+
+    let Ok((camera_transform, camera)) = camera_query.single() else {
+        return;
+    };
+
+    let Ok((player, player_transform)) = player_query.single() else {
+        return;
+    };
+
+    let look_target = player_transform.translation + Vec3::Y * 1.2;
+    let towards_player = look_target - camera_transform.translation;
+
+    let occluded = camera.occlusion_mode == CameraOcclusionMode::FadePlayer
+        && (towards_player.length() < FADE_DISTANCE
+            || Dir3::new(towards_player).is_ok_and(|direction| {
+                spatial_query
+                    .cast_ray(
+                        camera_transform.translation,
+                        direction,
+                        towards_player.length(),
+                        true,
+                        &SpatialQueryFilter::from_mask(view_blockers()),
+                    )
+                    .is_some()
+            }));
+
+    let current = alpha.unwrap_or(1.0);
+    let target = if occluded { FADED_ALPHA } else { 1.0 };
+    if current == target {
+        return;
+    }
+
+    let mut new_alpha = current.lerp(target, 1.0 - (-time.delta_secs() * FADE_SPEED).exp());
+    if (new_alpha - target).abs() < 0.01 {
+        new_alpha = target;
+    }
+    *alpha = Some(new_alpha);
+
+    for handle in children
+        .iter_descendants(player)
+        .filter_map(|e| mesh_materials.get(e).ok())
+    {
+        if let Some(material) = materials.get_mut(handle) {
+            material.base_color.set_alpha(new_alpha);
+            material.alpha_mode = if new_alpha < 1.0 {
+                AlphaMode::Blend
+            } else {
+                AlphaMode::Opaque
+            };
+        }
+    }
+}
+
+/// No blur when standing still, up to the maximum shutter angle when moving fast
+pub fn scale_motion_blur_with_speed(
+    mut camera_query: Query<&mut MotionBlur, With<ThirdPersonCamera>>,
+    player_query: Query<&LinearVelocity, With<bevy_tnua::prelude::TnuaController>>,
+    settings: Res<MotionBlurSettings>,
+    virtual_time: Res<Time<Virtual>>,
+) {
+    let Ok(mut motion_blur) = camera_query.single_mut() else {
+        return;
+    };
+
+    let Ok(player_velocity) = player_query.single() else {
+        return;
+    };
+
+    // Real time speed, so focusing doesn't take the blur away from the player
+    let speed = player_velocity.length() / real_time_compensation(&virtual_time);
+    motion_blur.shutter_angle = if settings.enabled {
+        settings.max_shutter_angle * (speed / FULL_BLUR_SPEED).min(1.0)
+    } else {
+        0.0
+    };
+}
+
+/// Copy the smoothing speeds of the selected feel onto the camera
+pub fn apply_camera_feel(
+    feel: Res<CameraFeel>,
+    mut cameras: Query<&mut ThirdPersonCamera>,
+    added: Query<(), Added<ThirdPersonCamera>>,
+) {
+    if !feel.is_changed() && added.is_empty() {
+        return;
+    }
+
+    let (follow_speed, rotation_smoothing, distance_smoothing) = feel.smoothing();
+    for mut camera in cameras.iter_mut() {
+        camera.follow_speed = follow_speed;
+        camera.rotation_smoothing = rotation_smoothing;
+        camera.distance_smoothing = distance_smoothing;
+    }
+}
+
+/// Fly around with WASD, Q and E while the free camera is on. The player controls ignore the
+/// keyboard in the meantime, so the player stands still.
+#[cfg(debug_assertions)]
+pub fn fly_free_camera(
+    mut free_camera: ResMut<FreeCamera>,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mut camera: Single<(&mut Transform, &ThirdPersonCamera)>,
+    time: Res<Time<Real>>,
+) {
+    if keyboard.just_pressed(FREE_CAMERA_KEY) {
+        // Turning it off lets the regular follow smoothing glide the camera back to the player
+        free_camera.active = !free_camera.active;
+    }
+
+    if !free_camera.active {
+        return;
+    }
+
+    let (transform, rig) = &mut *camera;
+
+    // Look the same way as the third person camera does at this yaw and pitch
+    let look = -Vec3::new(
+        rig.yaw.sin() * rig.pitch.cos(),
+        rig.pitch.sin(),
+        rig.yaw.cos() * rig.pitch.cos(),
+    );
+    transform.look_to(look, Vec3::Y);
+
+    let mut direction = Vec3::ZERO;
+    for (key, towards) in [
+        (KeyCode::KeyW, transform.forward().as_vec3()),
+        (KeyCode::KeyS, transform.back().as_vec3()),
+        (KeyCode::KeyA, transform.left().as_vec3()),
+        (KeyCode::KeyD, transform.right().as_vec3()),
+        (KeyCode::KeyE, Vec3::Y),
+        (KeyCode::KeyQ, Vec3::NEG_Y),
+    ] {
+        if keyboard.pressed(key) {
+            direction += towards;
+        }
+    }
+
+    let boost = if keyboard.pressed(KeyCode::ShiftLeft) {
+        2.0
+    } else {
+        1.0
+    };
+    transform.translation +=
+        direction.normalize_or_zero() * free_camera.speed * boost * time.delta_secs();
+}
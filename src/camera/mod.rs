@@ -9,10 +9,33 @@ pub struct ThirdPersonCameraPlugin;
 
 impl Plugin for ThirdPersonCameraPlugin {
     fn build(&self, app: &mut App) {
+        app.register_type::<controller::CursorLockSettings>();
+        app.init_resource::<controller::CursorLockSettings>();
         // Mouse input should be handled in Update for responsiveness
         app.add_systems(Update, controller::handle_mouse_look);
-        // Camera position updates should run in FixedUpdate to align with physics
-        // This prevents jitter when jumping or on moving platforms
-        app.add_systems(FixedUpdate, controller::update_camera_position);
+        app.add_systems(Update, controller::fade_occluded_player);
+        app.register_type::<controller::MotionBlurSettings>();
+        app.init_resource::<controller::MotionBlurSettings>();
+        app.add_systems(Update, controller::scale_motion_blur_with_speed);
+        app.register_type::<controller::CameraFeel>();
+        app.init_resource::<controller::CameraFeel>();
+        app.add_systems(Update, controller::apply_camera_feel);
+        // Follow the interpolated player every frame, rather than in fixed steps which stutter
+        // on displays faster than the physics rate. Before propagation so it shows this frame.
+        app.add_systems(
+            PostUpdate,
+            controller::update_camera_position
+                .before(TransformSystems::Propagate)
+                .run_if(controller::free_camera_inactive),
+        );
+        app.register_type::<controller::FreeCamera>();
+        app.init_resource::<controller::FreeCamera>();
+        #[cfg(debug_assertions)]
+        app.add_systems(
+            PreUpdate,
+            controller::fly_free_camera
+                .after(bevy::input::InputSystems)
+                .run_if(crate::console::console_closed),
+        );
     }
 }
@@ -1,8 +1,15 @@
 use avian3d::prelude::*;
-use bevy::{math::Affine2, mesh::Indices, platform::collections::HashMap, prelude::*};
+use bevy::{
+    math::Affine2,
+    mesh::Indices,
+    platform::{collections::HashMap, time::Instant},
+    prelude::*,
+};
 use noise::{NoiseFn, Perlin};
 
 use crate::assets::{GameAssets, MyStates};
+use crate::dungeon::Location;
+use crate::game::Surface;
 
 #[derive(Component)]
 pub struct ChunkObserver;
@@ -10,6 +17,104 @@ pub struct ChunkObserver;
 #[derive(Resource, Default, Deref, DerefMut)]
 pub struct ChunkIndex(HashMap<IVec2, Entity>);
 
+/// Seed of the procedural world, changing it regenerates all terrain
+#[derive(Resource, Reflect, Default, Clone, Copy, Deref, DerefMut)]
+#[reflect(Resource)]
+pub struct WorldSeed(pub u32);
+
+/// Parameters of the terrain noise, tweakable at runtime from the inspector
+#[derive(Resource, Reflect, Clone)]
+#[reflect(Resource)]
+pub struct TerrainSettings {
+    /// How zoomed in the noise is (lower = wider hills)
+    pub noise_scale: f64,
+    /// Height of the terrain at a noise value of 1.0
+    pub height_scale: f32,
+    /// Number of perlin layers stacked on top of each other
+    pub octaves: u32,
+    /// How fast the frequency should increase at each layer (sane = 2.0)
+    pub lacunarity: f64,
+    /// How much the influence should diminish at each layer [0 1]
+    pub persistance: f64,
+}
+
+impl Default for TerrainSettings {
+    fn default() -> Self {
+        Self {
+            noise_scale: 0.002,
+            height_scale: 16.0,
+            octaves: 8,
+            lacunarity: 2.0,
+            persistance: 0.6,
+        }
+    }
+}
+
+/// How much terrain is kept loaded around the player, in chunks
+#[derive(Resource, Reflect, Debug, Clone, Copy, PartialEq, Eq)]
+#[reflect(Resource)]
+pub struct RenderDistance {
+    /// Chunks up to this many chunks away in every direction are generated
+    pub load_radius: i32,
+    /// Chunks further away than this (manhattan distance) are unloaded again. Kept well above
+    /// twice the load radius, otherwise the corners of the ring get unloaded right away.
+    pub unload_distance: u32,
+}
+
+impl Default for RenderDistance {
+    fn default() -> Self {
+        Self {
+            load_radius: 1,
+            unload_distance: 50,
+        }
+    }
+}
+
+/// Whether chunk meshes share vertices between triangles, switchable to profile both
+#[derive(Resource, Reflect, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[reflect(Resource)]
+pub enum ChunkMeshMode {
+    #[default]
+    Indexed,
+    /// Every triangle gets its own three vertices
+    NonIndexed,
+}
+
+/// How long generating the chunk meshes takes, shown in the inspector
+#[derive(Resource, Reflect, Default)]
+#[reflect(Resource)]
+pub struct ChunkMeshStats {
+    pub mode: ChunkMeshMode,
+    pub chunks_generated: u32,
+    pub last_generation_ms: f32,
+    pub average_generation_ms: f32,
+}
+
+impl ChunkMeshStats {
+    fn record(&mut self, mode: ChunkMeshMode, milliseconds: f32) {
+        // Start over when switching modes, so the average only covers one of them
+        if mode != self.mode {
+            *self = ChunkMeshStats { mode, ..default() };
+        }
+
+        self.chunks_generated += 1;
+        self.last_generation_ms = milliseconds;
+        self.average_generation_ms +=
+            (milliseconds - self.average_generation_ms) / self.chunks_generated as f32;
+    }
+}
+
+/// Terrain debug views for checking seams and normals, toggled from the inspector
+#[cfg(debug_assertions)]
+#[derive(Resource, Reflect, Default)]
+#[reflect(Resource)]
+pub struct ChunkDebugView {
+    /// Draw all chunks as wireframe, not available on the web
+    pub wireframe: bool,
+    /// Draw the vertex normals of the chunk under the player
+    pub normals: bool,
+}
+
 pub struct ChunksPlugin;
 
 const FLOOR_SIZE: i32 = 8;
@@ -17,7 +122,35 @@ const FLOOR_SIZE: i32 = 8;
 impl Plugin for ChunksPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<ChunkIndex>();
-        app.add_systems(Update, update_chunk_index.run_if(in_state(MyStates::Next)));
+        app.register_type::<TerrainSettings>();
+        app.init_resource::<TerrainSettings>();
+        app.register_type::<WorldSeed>();
+        app.init_resource::<WorldSeed>();
+        app.register_type::<ChunkMeshMode>();
+        app.init_resource::<ChunkMeshMode>();
+        app.register_type::<ChunkMeshStats>();
+        app.init_resource::<ChunkMeshStats>();
+        app.register_type::<RenderDistance>();
+        app.init_resource::<RenderDistance>();
+        // No streaming while inside the dungeon, the chunks around the entrance stay loaded
+        app.add_systems(
+            Update,
+            update_chunk_index
+                .run_if(in_state(MyStates::Next).and(not(in_state(Location::Dungeon)))),
+        );
+        app.add_systems(
+            Update,
+            despawn_chunks_on_settings_change.run_if(terrain_changed),
+        );
+
+        #[cfg(debug_assertions)]
+        {
+            app.register_type::<ChunkDebugView>();
+            app.init_resource::<ChunkDebugView>();
+            app.add_systems(Update, draw_chunk_normals.run_if(in_state(MyStates::Next)));
+            #[cfg(not(target_arch = "wasm32"))]
+            app.add_systems(Update, update_chunk_wireframes);
+        }
     }
 }
 
@@ -25,12 +158,15 @@ fn update_chunk_index(
     mut commands: Commands,
     q: Single<(&GlobalTransform, &ChunkObserver)>,
     mut index: ResMut<ChunkIndex>,
+    distance: Res<RenderDistance>,
 ) {
     let (gt, _) = *q;
 
     let loc = gt.translation().xz().as_ivec2() / IVec2::splat(FLOOR_SIZE);
-    for y in -1..=1 {
-        for x in -1..=1 {
+    let radius = distance.load_radius.max(0);
+    let unload_distance = distance.unload_distance.max(radius as u32 * 2);
+    for y in -radius..=radius {
+        for x in -radius..=radius {
             let key = loc + IVec2::new(x, y);
             if !index.contains_key(&key) {
                 commands.run_system_cached_with(spawn_chunk, key);
@@ -39,7 +175,7 @@ fn update_chunk_index(
     }
 
     index.retain(|chunk_loc, entity| {
-        if loc.manhattan_distance(*chunk_loc) > 50 {
+        if loc.manhattan_distance(*chunk_loc) > unload_distance {
             commands.entity(*entity).despawn();
             false
         } else {
@@ -48,17 +184,108 @@ fn update_chunk_index(
     });
 }
 
+fn terrain_changed(
+    settings: Res<TerrainSettings>,
+    seed: Res<WorldSeed>,
+    mode: Res<ChunkMeshMode>,
+) -> bool {
+    (settings.is_changed() && !settings.is_added())
+        || (seed.is_changed() && !seed.is_added())
+        || (mode.is_changed() && !mode.is_added())
+}
+
+/// Throw away all loaded chunks so they get regenerated with the new settings
+fn despawn_chunks_on_settings_change(mut commands: Commands, mut index: ResMut<ChunkIndex>) {
+    for (_, entity) in index.drain() {
+        commands.entity(entity).despawn();
+    }
+}
+
+#[cfg(all(debug_assertions, not(target_arch = "wasm32")))]
+fn update_chunk_wireframes(
+    mut commands: Commands,
+    view: Res<ChunkDebugView>,
+    index: Res<ChunkIndex>,
+    wireframes: Query<(), With<bevy::pbr::wireframe::Wireframe>>,
+) {
+    // Also catches chunks spawned while the view is on
+    for entity in index.values() {
+        let has_wireframe = wireframes.contains(*entity);
+        if view.wireframe && !has_wireframe {
+            commands
+                .entity(*entity)
+                .insert(bevy::pbr::wireframe::Wireframe);
+        } else if !view.wireframe && has_wireframe {
+            commands
+                .entity(*entity)
+                .remove::<bevy::pbr::wireframe::Wireframe>();
+        }
+    }
+}
+
+/// Only for the chunk under the player, a whole 3x3 block of normals is way too many lines
+#[cfg(debug_assertions)]
+fn draw_chunk_normals(
+    view: Res<ChunkDebugView>,
+    index: Res<ChunkIndex>,
+    observer: Single<&GlobalTransform, With<ChunkObserver>>,
+    chunks: Query<(&Mesh3d, &GlobalTransform)>,
+    meshes: Res<Assets<Mesh>>,
+    mut gizmos: Gizmos,
+) {
+    use bevy::mesh::VertexAttributeValues::Float32x3;
+
+    if !view.normals {
+        return;
+    }
+
+    let loc = observer.translation().xz().as_ivec2() / IVec2::splat(FLOOR_SIZE);
+    let Some((mesh, transform)) = index.get(&loc).and_then(|entity| chunks.get(*entity).ok())
+    else {
+        return;
+    };
+    let Some(mesh) = meshes.get(&mesh.0) else {
+        return;
+    };
+    let (Some(Float32x3(positions)), Some(Float32x3(normals))) = (
+        mesh.attribute(Mesh::ATTRIBUTE_POSITION),
+        mesh.attribute(Mesh::ATTRIBUTE_NORMAL),
+    ) else {
+        return;
+    };
+
+    for (position, normal) in positions.iter().zip(normals) {
+        let start = transform.transform_point(Vec3::from(*position));
+        gizmos.line(
+            start,
+            start + Vec3::from(*normal) * 0.3,
+            Color::srgb(0.2, 0.6, 1.0),
+        );
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 fn spawn_chunk(
     In(offset): In<IVec2>,
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
     assets: Res<GameAssets>,
+    settings: Res<TerrainSettings>,
+    seed: Res<WorldSeed>,
+    mode: Res<ChunkMeshMode>,
+    mut stats: ResMut<ChunkMeshStats>,
     mut index: ResMut<ChunkIndex>,
 ) {
     // base - heightfield floor
     const FLOOR_RESOLUTION: usize = 100;
-    let (heightfield_mesh, heights) = generate_heightfield_mesh(offset, FLOOR_RESOLUTION);
+    let start = Instant::now();
+    let (mut heightfield_mesh, heights) =
+        generate_heightfield_mesh(offset, FLOOR_RESOLUTION, &settings, seed.0);
+    if *mode == ChunkMeshMode::NonIndexed {
+        heightfield_mesh.duplicate_vertices();
+    }
+    stats.record(*mode, start.elapsed().as_secs_f32() * 1000.0);
     let heightfield_handle = meshes.add(heightfield_mesh);
 
     let entity = commands
@@ -80,6 +307,7 @@ fn spawn_chunk(
                 heights,
                 Vec3::new(FLOOR_SIZE as f32, 1.0, FLOOR_SIZE as f32),
             ),
+            Surface::Grass,
         ))
         .id();
 
@@ -96,13 +324,27 @@ struct LayeredPerlin {
 
 impl LayeredPerlin {
     fn new(num_layers: u32) -> Self {
+        Self::with_seed(num_layers, 0)
+    }
+
+    fn with_seed(num_layers: u32, seed: u32) -> Self {
         LayeredPerlin {
-            layers: (0u32..num_layers).map(Perlin::new).collect(),
+            layers: (0u32..num_layers)
+                .map(|layer| Perlin::new(seed.wrapping_add(layer)))
+                .collect(),
             lacunarity: 2.0,
             persistance: 0.6,
         }
     }
 
+    fn from_settings(settings: &TerrainSettings, seed: u32) -> Self {
+        LayeredPerlin {
+            lacunarity: settings.lacunarity,
+            persistance: settings.persistance,
+            ..LayeredPerlin::with_seed(settings.octaves, seed)
+        }
+    }
+
     fn get(&self, x: f64, z: f64) -> f64 {
         let mut frequency = 1.0;
         let mut amplitude = 1.0;
@@ -118,12 +360,26 @@ impl LayeredPerlin {
     }
 }
 
+fn sample_height(perlin: &LayeredPerlin, settings: &TerrainSettings, x: f64, z: f64) -> f32 {
+    perlin.get(x * settings.noise_scale, z * settings.noise_scale) as f32 * settings.height_scale
+}
+
+/// Height of the terrain surface at a world position. This evaluates the same noise as the
+/// chunks, so it works for places that aren't loaded yet and needs no physics raycast.
+pub fn terrain_height_at(world_x: f32, world_z: f32, settings: &TerrainSettings, seed: u32) -> f32 {
+    let perlin = LayeredPerlin::from_settings(settings, seed);
+    sample_height(&perlin, settings, world_x as f64, world_z as f64)
+}
+
 /// Generate a heightfield mesh and height data using Perlin noise
 /// Returns (mesh, heights) where heights is a 2D array for the collider
-fn generate_heightfield_mesh(offset: IVec2, resolution: usize) -> (Mesh, Vec<Vec<f32>>) {
-    let perlin = LayeredPerlin::new(8);
-    let noise_scale = 0.002;
-    let height_scale = 16.0;
+fn generate_heightfield_mesh(
+    offset: IVec2,
+    resolution: usize,
+    settings: &TerrainSettings,
+    seed: u32,
+) -> (Mesh, Vec<Vec<f32>>) {
+    let perlin = LayeredPerlin::from_settings(settings, seed);
 
     let mut positions = Vec::new();
     let mut uvs = Vec::new();
@@ -138,11 +394,12 @@ fn generate_heightfield_mesh(offset: IVec2, resolution: usize) -> (Mesh, Vec<Vec
             let z_pos = (z as f32 / resolution as f32 - 0.5) * FLOOR_SIZE as f32;
 
             // Sample Perlin noise for height
-            let height = perlin.get(
-                ((offset.x * FLOOR_SIZE) as f64 + x_pos as f64) * noise_scale,
-                ((offset.y * FLOOR_SIZE) as f64 + z_pos as f64) * noise_scale,
-            ) as f32
-                * height_scale;
+            let height = sample_height(
+                &perlin,
+                settings,
+                (offset.x * FLOOR_SIZE) as f64 + x_pos as f64,
+                (offset.y * FLOOR_SIZE) as f64 + z_pos as f64,
+            );
 
             positions.push([x_pos, height, z_pos]);
             uvs.push([x as f32 / resolution as f32, z as f32 / resolution as f32]);
@@ -191,6 +448,7 @@ fn generate_heightfield_mesh(offset: IVec2, resolution: usize) -> (Mesh, Vec<Vec
 #[cfg(test)]
 mod tests {
     use super::*;
+    use bevy::mesh::VertexAttributeValues;
     use std::fs::File;
     use std::io::Write;
 
@@ -240,4 +498,28 @@ mod tests {
 
         println!("Generated layered_perlin_noise.ppm (256x256)");
     }
+
+    #[test]
+    fn test_terrain_height_at_matches_mesh_vertices() {
+        const RESOLUTION: usize = 10;
+        let settings = TerrainSettings::default();
+        let seed = 1234;
+        let offset = IVec2::new(3, -2);
+
+        let (mesh, heights) = generate_heightfield_mesh(offset, RESOLUTION, &settings, seed);
+        let Some(VertexAttributeValues::Float32x3(positions)) =
+            mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+        else {
+            panic!("heightfield mesh has no positions");
+        };
+
+        for (i, [x, y, z]) in positions.iter().enumerate() {
+            let world_x = (offset.x * FLOOR_SIZE) as f32 + x;
+            let world_z = (offset.y * FLOOR_SIZE) as f32 + z;
+            let expected = terrain_height_at(world_x, world_z, &settings, seed);
+
+            assert!((expected - y).abs() < 1e-4, "vertex {i}: {expected} != {y}");
+            assert_eq!(*y, heights[i / (RESOLUTION + 1)][i % (RESOLUTION + 1)]);
+        }
+    }
 }
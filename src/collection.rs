@@ -0,0 +1,181 @@
+use bevy::platform::collections::HashMap;
+use bevy::prelude::*;
+
+use crate::assets::MyStates;
+use crate::hud::{HudRoot, HudSettings, spawn_hud_root};
+
+/// Keeps track of the props the player collected, with a toast for every new kind
+pub struct CollectionPlugin;
+
+pub const COLLECTION_KEY: KeyCode = KeyCode::Tab;
+const TOAST_DURATION: f32 = 2.5;
+const PANEL_ALPHA: f32 = 0.6;
+const TEXT_COLOR: Color = Color::srgb(1.0, 0.95, 0.8);
+const TOAST_COLOR: Color = Color::srgb(1.0, 0.85, 0.0);
+
+/// The kinds of props that can be collected
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Reflect)]
+pub enum ItemKind {
+    Wineglass,
+    Trophy,
+    Bong,
+}
+
+impl ItemKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            ItemKind::Wineglass => "Wineglass",
+            ItemKind::Trophy => "Trophy",
+            ItemKind::Bong => "Bong",
+        }
+    }
+}
+
+/// Sent when the player picks up a prop
+#[derive(Message)]
+pub struct ItemCollected(pub ItemKind);
+
+/// How many of each kind of prop the player collected
+#[derive(Resource, Reflect, Default)]
+#[reflect(Resource)]
+pub struct Collection {
+    pub counts: HashMap<ItemKind, u32>,
+}
+
+#[derive(Component)]
+struct CollectionPanel;
+
+#[derive(Component)]
+struct Toast(Timer);
+
+impl Plugin for CollectionPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_message::<ItemCollected>();
+        app.register_type::<Collection>();
+        app.init_resource::<Collection>();
+        app.add_systems(
+            OnEnter(MyStates::Next),
+            spawn_collection_panel.after(spawn_hud_root),
+        );
+        app.add_systems(
+            Update,
+            (
+                record_collected_items,
+                update_collection_panel,
+                expire_toasts,
+            )
+                .chain()
+                .run_if(in_state(MyStates::Next)),
+        );
+    }
+}
+
+fn spawn_collection_panel(mut commands: Commands, hud: Single<Entity, With<HudRoot>>) {
+    commands.spawn((
+        CollectionPanel,
+        ChildOf(*hud),
+        Name::new("Collection"),
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(20.0),
+            left: Val::Px(20.0),
+            padding: UiRect::all(Val::Px(12.0)),
+            ..default()
+        },
+        BackgroundColor(Color::srgba(0.0, 0.0, 0.0, PANEL_ALPHA)),
+        Pickable::IGNORE,
+        Visibility::Hidden,
+        Text::default(),
+        TextFont {
+            font_size: 14.0,
+            ..default()
+        },
+        TextColor(TEXT_COLOR),
+    ));
+}
+
+fn record_collected_items(
+    mut commands: Commands,
+    mut collected: MessageReader<ItemCollected>,
+    mut collection: ResMut<Collection>,
+    hud: Single<Entity, With<HudRoot>>,
+    settings: Res<HudSettings>,
+) {
+    for ItemCollected(kind) in collected.read() {
+        let count = collection.counts.entry(*kind).or_default();
+        *count += 1;
+
+        if *count == 1 {
+            commands.spawn((
+                Toast(Timer::from_seconds(TOAST_DURATION, TimerMode::Once)),
+                ChildOf(*hud),
+                Node {
+                    position_type: PositionType::Absolute,
+                    top: Val::Percent(20.0),
+                    width: Val::Percent(100.0),
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                Pickable::IGNORE,
+                children![(
+                    Text::new(format!("{} collected!", kind.name())),
+                    TextFont {
+                        font_size: 24.0,
+                        ..default()
+                    },
+                    TextColor(TOAST_COLOR.with_alpha(settings.alpha())),
+                    TextShadow::default(),
+                )],
+            ));
+        }
+    }
+}
+
+/// Hold the collection key to see what has been collected so far
+#[allow(clippy::type_complexity)]
+fn update_collection_panel(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    collection: Res<Collection>,
+    settings: Res<HudSettings>,
+    mut panel: Single<
+        (
+            &mut Text,
+            &mut Visibility,
+            &mut BackgroundColor,
+            &mut TextColor,
+        ),
+        With<CollectionPanel>,
+    >,
+) {
+    let (text, visibility, background, color) = &mut *panel;
+
+    if !keyboard.pressed(COLLECTION_KEY) {
+        **visibility = Visibility::Hidden;
+        return;
+    }
+    **visibility = Visibility::Inherited;
+    background.0.set_alpha(PANEL_ALPHA * settings.alpha());
+    color.0 = TEXT_COLOR.with_alpha(settings.alpha());
+
+    if collection.is_changed() || text.0.is_empty() {
+        let mut lines: Vec<_> = collection
+            .counts
+            .iter()
+            .map(|(kind, count)| format!("{}: {count}", kind.name()))
+            .collect();
+        lines.sort();
+        if lines.is_empty() {
+            lines.push("Nothing collected yet".to_string());
+        }
+        text.0 = lines.join("\n");
+    }
+}
+
+fn expire_toasts(mut commands: Commands, mut toasts: Query<(Entity, &mut Toast)>, time: Res<Time>) {
+    for (entity, mut toast) in toasts.iter_mut() {
+        toast.0.tick(time.delta());
+        if toast.0.is_finished() {
+            commands.entity(entity).despawn();
+        }
+    }
+}
@@ -0,0 +1,208 @@
+use avian3d::prelude::LinearVelocity;
+use bevy::input::InputSystems;
+use bevy::input::keyboard::{Key, KeyboardInput};
+use bevy::prelude::*;
+
+use crate::assets::MyStates;
+use crate::chunks::{TerrainSettings, WorldSeed, terrain_height_at};
+use crate::dungeon::{DungeonEntrance, Location};
+use crate::player::controller::PlayerRoot;
+
+/// Developer console to teleport around and tweak the world while testing
+pub struct ConsolePlugin;
+
+pub const CONSOLE_KEY: KeyCode = KeyCode::Backquote;
+/// Lines of output kept on screen
+const MAX_LOG_LINES: usize = 8;
+/// Height of the player above the ground when put onto it, the same as at spawn
+const PLAYER_ABOVE_GROUND: f32 = 0.85 + 0.1;
+
+#[derive(Resource, Default)]
+pub struct Console {
+    open: bool,
+    input: String,
+    log: Vec<String>,
+}
+
+impl Console {
+    fn print(&mut self, line: impl Into<String>) {
+        self.log.push(line.into());
+        let excess = self.log.len().saturating_sub(MAX_LOG_LINES);
+        self.log.drain(..excess);
+    }
+}
+
+/// Run condition for systems reading gameplay keys, so typing into the console doesn't also
+/// play the game. Always true when the console isn't there (release builds).
+pub fn console_closed(console: Option<Res<Console>>) -> bool {
+    console.is_none_or(|console| !console.open)
+}
+
+#[derive(Component)]
+struct ConsoleRoot;
+
+#[derive(Component)]
+struct ConsoleText;
+
+impl Plugin for ConsolePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Console>();
+        app.add_systems(OnEnter(MyStates::Next), spawn_console);
+        // Before the game reads the keyboard, so the console closes and opens in the same frame
+        // for everything else
+        app.add_systems(
+            PreUpdate,
+            console_input
+                .after(InputSystems)
+                .run_if(in_state(MyStates::Next)),
+        );
+        app.add_systems(
+            Update,
+            update_console_text.run_if(in_state(MyStates::Next).and(resource_changed::<Console>)),
+        );
+    }
+}
+
+fn spawn_console(mut commands: Commands) {
+    commands.spawn((
+        ConsoleRoot,
+        Name::new("Console"),
+        Node {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.0),
+            padding: UiRect::all(Val::Px(8.0)),
+            ..default()
+        },
+        BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.75)),
+        Visibility::Hidden,
+        children![(
+            ConsoleText,
+            Text::default(),
+            TextFont {
+                font_size: 14.0,
+                ..default()
+            },
+            TextColor(Color::srgb(0.8, 1.0, 0.8)),
+        )],
+    ));
+}
+
+fn console_input(
+    mut commands: Commands,
+    mut console: ResMut<Console>,
+    mut keys: MessageReader<KeyboardInput>,
+    keyboard: Res<ButtonInput<KeyCode>>,
+) {
+    if keyboard.just_pressed(CONSOLE_KEY) {
+        console.open = !console.open;
+        keys.clear();
+        return;
+    }
+
+    if !console.open {
+        keys.clear();
+        return;
+    }
+
+    for key in keys.read() {
+        if !key.state.is_pressed() {
+            continue;
+        }
+
+        match &key.logical_key {
+            Key::Enter => {
+                let line = std::mem::take(&mut console.input);
+                commands.run_system_cached_with(run_console_command, line);
+            }
+            Key::Backspace => {
+                console.input.pop();
+            }
+            Key::Escape => {
+                console.open = false;
+            }
+            Key::Space => console.input.push(' '),
+            Key::Character(text) => console.input.push_str(text),
+            _ => {}
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn run_console_command(
+    In(line): In<String>,
+    mut console: ResMut<Console>,
+    mut player: Query<(&mut Transform, &mut LinearVelocity), With<PlayerRoot>>,
+    mut entrances: Query<&mut Transform, (With<DungeonEntrance>, Without<PlayerRoot>)>,
+    mut seed: ResMut<WorldSeed>,
+    terrain: Res<TerrainSettings>,
+    location: Res<State<Location>>,
+) {
+    // Down in the dungeon the terrain is far overhead
+    let on_terrain = *location.get() == Location::Overworld;
+
+    console.print(format!("> {line}"));
+
+    let words: Vec<&str> = line.split_whitespace().collect();
+    match words.as_slice() {
+        [] => {}
+        ["help"] => {
+            console.print("tp <x> <y> <z>  teleport the player");
+            console.print("seed <n>        regenerate the world with another seed");
+        }
+        ["tp", x, y, z] => {
+            let (Ok(x), Ok(y), Ok(z)) = (x.parse(), y.parse(), z.parse()) else {
+                console.print("usage: tp <x> <y> <z>");
+                return;
+            };
+            // Never below the ground, the player would fall through the world
+            let y = if on_terrain {
+                y.max(terrain_height_at(x, z, &terrain, seed.0) + PLAYER_ABOVE_GROUND)
+            } else {
+                y
+            };
+            for (mut transform, mut velocity) in player.iter_mut() {
+                transform.translation = Vec3::new(x, y, z);
+                velocity.0 = Vec3::ZERO;
+            }
+        }
+        ["seed", n] => {
+            let Ok(n) = n.parse() else {
+                console.print("usage: seed <n>");
+                return;
+            };
+            seed.0 = n;
+
+            // The ground moves, put the player and the dungeon entrance back on top of it
+            if on_terrain {
+                for (mut transform, mut velocity) in player.iter_mut() {
+                    let position = transform.translation;
+                    transform.translation.y =
+                        terrain_height_at(position.x, position.z, &terrain, n)
+                            + PLAYER_ABOVE_GROUND;
+                    velocity.0 = Vec3::ZERO;
+                }
+            }
+            for mut transform in entrances.iter_mut() {
+                let position = transform.translation;
+                transform.translation.y = terrain_height_at(position.x, position.z, &terrain, n);
+            }
+        }
+        [command, ..] => console.print(format!("unknown command '{command}', try 'help'")),
+    }
+}
+
+fn update_console_text(
+    console: Res<Console>,
+    mut root: Single<&mut Visibility, With<ConsoleRoot>>,
+    mut text: Single<&mut Text, With<ConsoleText>>,
+) {
+    **root = if console.open {
+        Visibility::Inherited
+    } else {
+        Visibility::Hidden
+    };
+
+    let mut lines = console.log.clone();
+    lines.push(format!("> {}_", console.input));
+    text.0 = lines.join("\n");
+}
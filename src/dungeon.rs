@@ -0,0 +1,304 @@
+use avian3d::prelude::*;
+use bevy::platform::collections::HashSet;
+use bevy::{math::Affine2, prelude::*};
+use rand::{Rng, SeedableRng, rngs::StdRng};
+
+use crate::assets::{GameAssets, MyStates};
+use crate::camera::ThirdPersonCamera;
+use crate::chunks::WorldSeed;
+use crate::game::Surface;
+use crate::player::controller::PlayerRoot;
+use crate::spawners::SpawnTorch;
+
+/// An archway in the overworld leading down into a generated indoor dungeon
+pub struct DungeonPlugin;
+
+/// Whether the player is out on the streamed terrain or inside the dungeon
+#[derive(SubStates, Clone, PartialEq, Eq, Hash, Debug, Default)]
+#[source(MyStates = MyStates::Next)]
+pub enum Location {
+    #[default]
+    Overworld,
+    Dungeon,
+}
+
+/// Walk through it to enter the dungeon
+#[derive(Component)]
+#[require(Transform, InheritedVisibility)]
+pub struct DungeonEntrance;
+
+/// Step onto it to go back up
+#[derive(Component)]
+struct DungeonExit;
+
+/// Where to put the player when they leave the dungeon
+#[derive(Resource)]
+struct ReturnPosition(Vec3);
+
+/// The dungeon is built this far below the entrance, out of sight of the overworld
+const DUNGEON_DEPTH: f32 = 200.0;
+const ROOM_COUNT: usize = 8;
+const ROOM_SIZE: f32 = 8.0;
+const ROOM_HEIGHT: f32 = 4.0;
+const WALL_THICKNESS: f32 = 0.5;
+const DOOR_WIDTH: f32 = 2.0;
+/// How close the player has to get to an entrance or exit to take it
+const TRIGGER_RADIUS: f32 = 0.8;
+
+impl Plugin for DungeonPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_sub_state::<Location>();
+        app.add_observer(on_spawn_entrance);
+        app.add_systems(Update, enter_dungeon.run_if(in_state(Location::Overworld)));
+        app.add_systems(Update, leave_dungeon.run_if(in_state(Location::Dungeon)));
+        app.add_systems(OnEnter(Location::Dungeon), generate_dungeon);
+        app.add_systems(OnExit(Location::Dungeon), return_to_entrance);
+    }
+}
+
+fn on_spawn_entrance(
+    on: On<Add, DungeonEntrance>,
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let stone = materials.add(StandardMaterial {
+        base_color_texture: Some(assets.stones.clone()),
+        perceptual_roughness: 0.9,
+        ..default()
+    });
+
+    let root = on.event_target();
+    commands.entity(root).insert(Name::new("Dungeon Entrance"));
+
+    // Two pillars with a lintel on top
+    for (size, position) in [
+        (Vec3::new(0.6, 3.0, 0.6), Vec3::new(-1.3, 1.5, 0.0)),
+        (Vec3::new(0.6, 3.0, 0.6), Vec3::new(1.3, 1.5, 0.0)),
+        (Vec3::new(3.2, 0.6, 0.6), Vec3::new(0.0, 3.3, 0.0)),
+    ] {
+        commands.spawn((
+            ChildOf(root),
+            Mesh3d(meshes.add(Cuboid::from_size(size))),
+            MeshMaterial3d(stone.clone()),
+            Transform::from_translation(position),
+            RigidBody::Static,
+            Collider::cuboid(size.x, size.y, size.z),
+            Surface::Stone,
+        ));
+    }
+
+    // Pitch black opening, without a collider so it can be walked into
+    commands.spawn((
+        ChildOf(root),
+        Mesh3d(meshes.add(Cuboid::new(2.0, 3.0, 0.05))),
+        MeshMaterial3d(materials.add(StandardMaterial {
+            base_color: Color::BLACK,
+            unlit: true,
+            ..default()
+        })),
+        Transform::from_xyz(0.0, 1.5, 0.0),
+    ));
+}
+
+/// Put the player somewhere else, and the camera along with them so it doesn't fly all the way
+fn teleport(
+    player: &mut Transform,
+    velocity: &mut LinearVelocity,
+    camera: &mut Transform,
+    to: Vec3,
+) {
+    camera.translation += to - player.translation;
+    player.translation = to;
+    velocity.0 = Vec3::ZERO;
+}
+
+fn enter_dungeon(
+    mut commands: Commands,
+    player: Single<&Transform, With<PlayerRoot>>,
+    entrances: Query<&GlobalTransform, With<DungeonEntrance>>,
+    mut location: ResMut<NextState<Location>>,
+) {
+    for entrance in entrances.iter() {
+        let offset = player.translation - entrance.translation();
+        if offset.xz().length() < TRIGGER_RADIUS && offset.y.abs() < 2.0 {
+            // Come back out in front of the arch, not inside it
+            let in_front = entrance.transform_point(Vec3::new(0.0, 1.0, 2.5));
+            commands.insert_resource(ReturnPosition(in_front));
+            location.set(Location::Dungeon);
+            return;
+        }
+    }
+}
+
+fn leave_dungeon(
+    player: Single<&Transform, With<PlayerRoot>>,
+    exits: Query<&GlobalTransform, With<DungeonExit>>,
+    mut location: ResMut<NextState<Location>>,
+) {
+    for exit in exits.iter() {
+        let offset = player.translation - exit.translation();
+        if offset.xz().length() < TRIGGER_RADIUS && offset.y.abs() < 2.0 {
+            location.set(Location::Overworld);
+            return;
+        }
+    }
+}
+
+fn return_to_entrance(
+    position: Res<ReturnPosition>,
+    mut player: Single<(&mut Transform, &mut LinearVelocity), With<PlayerRoot>>,
+    mut camera: Single<&mut Transform, (With<ThirdPersonCamera>, Without<PlayerRoot>)>,
+) {
+    let (transform, velocity) = &mut *player;
+    teleport(transform, velocity, &mut camera, position.0);
+}
+
+/// Rooms on a grid, connected by doors. A random walk keeps every room reachable. The same
+/// seed always gives the same layout.
+fn generate_layout(seed: u32) -> (Vec<IVec2>, HashSet<(IVec2, IVec2)>) {
+    const DIRECTIONS: [IVec2; 4] = [IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y];
+
+    let mut rng = StdRng::seed_from_u64(seed as u64);
+    let mut rooms = vec![IVec2::ZERO];
+    let mut doors = HashSet::new();
+    let mut current = IVec2::ZERO;
+    while rooms.len() < ROOM_COUNT {
+        let next = current + DIRECTIONS[rng.random_range(0..DIRECTIONS.len())];
+        // Both ways, so each room can look up its own doors
+        doors.insert((current, next));
+        doors.insert((next, current));
+        if !rooms.contains(&next) {
+            rooms.push(next);
+        }
+        current = next;
+    }
+
+    (rooms, doors)
+}
+
+fn generate_dungeon(
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    position: Res<ReturnPosition>,
+    seed: Res<WorldSeed>,
+    mut player: Single<(&mut Transform, &mut LinearVelocity), With<PlayerRoot>>,
+    mut camera: Single<&mut Transform, (With<ThirdPersonCamera>, Without<PlayerRoot>)>,
+) {
+    let origin = position.0.with_y(position.0.y - DUNGEON_DEPTH);
+    let (rooms, doors) = generate_layout(seed.0);
+
+    let stone = materials.add(StandardMaterial {
+        base_color_texture: Some(assets.mossy_stones.clone()),
+        uv_transform: Affine2::from_scale(Vec2::splat(2.0)),
+        perceptual_roughness: 1.0,
+        ..default()
+    });
+    let mut spawn_block = |commands: &mut Commands, center: Vec3, size: Vec3| {
+        commands.spawn((
+            Mesh3d(meshes.add(Cuboid::from_size(size))),
+            MeshMaterial3d(stone.clone()),
+            Transform::from_translation(center),
+            RigidBody::Static,
+            Collider::cuboid(size.x, size.y, size.z),
+            Surface::Stone,
+            DespawnOnExit(Location::Dungeon),
+        ));
+    };
+
+    for room in rooms.iter() {
+        let center = origin + Vec3::new(room.x as f32, 0.0, room.y as f32) * ROOM_SIZE;
+
+        // Floor and ceiling
+        let slab = Vec3::new(ROOM_SIZE, WALL_THICKNESS, ROOM_SIZE);
+        spawn_block(&mut commands, center - Vec3::Y * WALL_THICKNESS / 2.0, slab);
+        spawn_block(
+            &mut commands,
+            center + Vec3::Y * (ROOM_HEIGHT + WALL_THICKNESS / 2.0),
+            slab,
+        );
+
+        // Every room has its own walls just inside its edges, with a gap where there's a door
+        for direction in [IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y] {
+            let normal = Vec3::new(direction.x as f32, 0.0, direction.y as f32);
+            let along = Vec3::new(direction.y.abs() as f32, 0.0, direction.x.abs() as f32);
+            let wall_center =
+                center + normal * (ROOM_SIZE - WALL_THICKNESS) / 2.0 + Vec3::Y * ROOM_HEIGHT / 2.0;
+            let wall_size = |length: f32| {
+                normal.abs() * WALL_THICKNESS + along * length + Vec3::Y * ROOM_HEIGHT
+            };
+
+            if doors.contains(&(*room, *room + direction)) {
+                let length = (ROOM_SIZE - DOOR_WIDTH) / 2.0;
+                let offset = along * (DOOR_WIDTH + length) / 2.0;
+                spawn_block(&mut commands, wall_center + offset, wall_size(length));
+                spawn_block(&mut commands, wall_center - offset, wall_size(length));
+            } else {
+                spawn_block(&mut commands, wall_center, wall_size(ROOM_SIZE));
+            }
+        }
+
+        commands.spawn((
+            SpawnTorch,
+            Transform::from_translation(center + Vec3::new(2.5, 1.0, 2.5)),
+            DespawnOnExit(Location::Dungeon),
+        ));
+    }
+
+    // The way back up, in a corner of the first room
+    commands.spawn((
+        DungeonExit,
+        Name::new("Dungeon Exit"),
+        Mesh3d(meshes.add(Cylinder::new(0.8, 0.1))),
+        MeshMaterial3d(materials.add(StandardMaterial {
+            base_color: Color::srgb(0.6, 0.8, 1.0),
+            emissive: LinearRgba::rgb(2.0, 3.0, 6.0),
+            ..default()
+        })),
+        Transform::from_translation(origin + Vec3::new(-2.5, 0.05, -2.5)),
+        DespawnOnExit(Location::Dungeon),
+    ));
+
+    let (transform, velocity) = &mut *player;
+    teleport(transform, velocity, &mut camera, origin + Vec3::Y * 1.0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layout_rooms_are_all_reachable() {
+        for seed in 0..100 {
+            let (rooms, doors) = generate_layout(seed);
+
+            assert_eq!(rooms.len(), ROOM_COUNT);
+            let distinct: HashSet<IVec2> = rooms.iter().copied().collect();
+            assert_eq!(
+                distinct.len(),
+                ROOM_COUNT,
+                "seed {seed} has duplicate rooms"
+            );
+
+            // Flood fill through the doors from the first room
+            let mut reached: HashSet<IVec2> = [rooms[0]].into_iter().collect();
+            let mut frontier = vec![rooms[0]];
+            while let Some(room) = frontier.pop() {
+                for &(from, to) in doors.iter() {
+                    if from == room && reached.insert(to) {
+                        frontier.push(to);
+                    }
+                }
+            }
+            assert_eq!(reached, distinct, "seed {seed} has unreachable rooms");
+        }
+    }
+
+    #[test]
+    fn test_layout_is_deterministic() {
+        assert_eq!(generate_layout(42), generate_layout(42));
+    }
+}
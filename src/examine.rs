@@ -0,0 +1,151 @@
+use avian3d::prelude::*;
+use bevy::prelude::*;
+
+use crate::assets::MyStates;
+use crate::game::Pickupable;
+use crate::platform::PlatformPath;
+use crate::player::controller::{PlayerRoot, view_blockers};
+use crate::spawners::SpawnTorch;
+
+/// Hold a key to show the names of interactable things around the player
+pub struct ExaminePlugin;
+
+pub const EXAMINE_KEY: KeyCode = KeyCode::AltLeft;
+const EXAMINE_RADIUS: f32 = 8.0;
+/// Labels are pooled, so this is also the maximum amount shown at once
+const MAX_LABELS: usize = 12;
+/// Labels float slightly above the entity origin
+const LABEL_HEIGHT: f32 = 0.5;
+
+#[derive(Component)]
+struct ExamineRoot;
+
+#[derive(Component)]
+struct ExamineLabel;
+
+impl Plugin for ExaminePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(MyStates::Next), spawn_examine_labels);
+        app.add_systems(
+            Update,
+            update_examine_labels.run_if(in_state(MyStates::Next)),
+        );
+    }
+}
+
+fn spawn_examine_labels(mut commands: Commands) {
+    commands
+        .spawn((
+            ExamineRoot,
+            Name::new("Examine Labels"),
+            Node {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                ..default()
+            },
+            Visibility::Hidden,
+        ))
+        .with_children(|parent| {
+            for _ in 0..MAX_LABELS {
+                parent.spawn((
+                    ExamineLabel,
+                    Text::default(),
+                    TextFont {
+                        font_size: 14.0,
+                        ..default()
+                    },
+                    TextColor(Color::srgb(1.0, 0.95, 0.8)),
+                    TextShadow::default(),
+                    Node {
+                        position_type: PositionType::Absolute,
+                        ..default()
+                    },
+                    Visibility::Hidden,
+                ));
+            }
+        });
+}
+
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
+fn update_examine_labels(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mut root: Single<&mut Visibility, With<ExamineRoot>>,
+    mut labels: Query<
+        (&mut Node, &mut Text, &mut Visibility),
+        (With<ExamineLabel>, Without<ExamineRoot>),
+    >,
+    camera: Single<(&Camera, &GlobalTransform)>,
+    player: Single<&GlobalTransform, With<PlayerRoot>>,
+    interactables: Query<
+        (Entity, &Name, &GlobalTransform),
+        Or<(With<Pickupable>, With<PlatformPath>, With<SpawnTorch>)>,
+    >,
+    parents: Query<&ChildOf>,
+    spatial_query: SpatialQuery,
+    ui_scale: Res<UiScale>,
+) {
+    if !keyboard.pressed(EXAMINE_KEY) {
+        **root = Visibility::Hidden;
+        return;
+    }
+    **root = Visibility::Inherited;
+
+    let (camera, camera_transform) = *camera;
+    let camera_pos = camera_transform.translation();
+    let player_pos = player.translation();
+
+    let mut nearby: Vec<_> = interactables
+        .iter()
+        .map(|(e, name, gt)| {
+            (
+                e,
+                name,
+                gt.translation(),
+                gt.translation().distance(player_pos),
+            )
+        })
+        .filter(|(.., distance)| *distance <= EXAMINE_RADIUS)
+        .collect();
+    nearby.sort_by(|a, b| a.3.total_cmp(&b.3));
+
+    let filter = SpatialQueryFilter::from_mask(view_blockers());
+    let in_sight = |target: Entity, pos: Vec3| {
+        let Ok(direction) = Dir3::new(pos - camera_pos) else {
+            return true;
+        };
+        match spatial_query.cast_ray(
+            camera_pos,
+            direction,
+            pos.distance(camera_pos),
+            true,
+            &filter,
+        ) {
+            None => true,
+            // torches (and the like) have their collider on a child
+            Some(hit) => {
+                hit.entity == target || parents.iter_ancestors(hit.entity).any(|a| a == target)
+            }
+        }
+    };
+
+    let mut visible = nearby
+        .into_iter()
+        .filter(|(e, _, pos, _)| in_sight(*e, *pos))
+        .filter_map(|(_, name, pos, _)| {
+            let screen = camera
+                .world_to_viewport(camera_transform, pos + Vec3::Y * LABEL_HEIGHT)
+                .ok()?;
+            Some((name, screen / ui_scale.0))
+        });
+
+    for (mut node, mut text, mut visibility) in labels.iter_mut() {
+        if let Some((name, screen)) = visible.next() {
+            node.left = Val::Px(screen.x);
+            node.top = Val::Px(screen.y);
+            text.0 = name.to_string();
+            *visibility = Visibility::Inherited;
+        } else {
+            *visibility = Visibility::Hidden;
+        }
+    }
+}
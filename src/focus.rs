@@ -0,0 +1,173 @@
+use bevy::prelude::*;
+
+use crate::assets::MyStates;
+use crate::console::{Console, console_closed};
+use crate::hud::{HudRoot, HudSettings, spawn_hud_root};
+
+/// Hold a key to slow down the world (bullet-time) while the player keeps moving at their
+/// normal speed, at the cost of a focus meter
+pub struct FocusPlugin;
+
+pub const FOCUS_KEY: KeyCode = KeyCode::KeyF;
+/// Relative speed of virtual time while focusing
+const FOCUS_TIME_SCALE: f32 = 0.35;
+/// Fraction of the meter needed to start focusing, so tapping the key on an empty meter
+/// doesn't flicker the time scale
+const MIN_FOCUS_TO_START: f32 = 0.2;
+const METER_BACKGROUND_ALPHA: f32 = 0.6;
+const METER_COLOR: Color = Color::srgb(0.5, 0.8, 1.0);
+
+/// The focus meter, drained while focusing and refilled otherwise
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct Focus {
+    pub current: f32,
+    pub max: f32,
+    /// Meter drained per real second while focusing
+    pub drain: f32,
+    /// Meter regained per real second while not focusing
+    pub regen: f32,
+    pub active: bool,
+}
+
+impl Default for Focus {
+    fn default() -> Self {
+        Self {
+            current: 100.0,
+            max: 100.0,
+            drain: 25.0,
+            regen: 10.0,
+            active: false,
+        }
+    }
+}
+
+/// Bar showing the focus meter, hidden while it is full
+#[derive(Component)]
+struct FocusMeter;
+
+/// The filled part of the focus meter
+#[derive(Component)]
+struct FocusMeterFill;
+
+impl Plugin for FocusPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Focus>();
+        app.init_resource::<Focus>();
+        app.add_systems(
+            OnEnter(MyStates::Next),
+            spawn_focus_meter.after(spawn_hud_root),
+        );
+        app.add_systems(
+            Update,
+            (update_focus, update_focus_meter)
+                .chain()
+                .run_if(in_state(MyStates::Next)),
+        );
+    }
+}
+
+/// Multiplier that turns a duration or speed in (possibly slowed) virtual time back into
+/// real time. Used for things that should not feel the slowdown, like the player and camera.
+pub fn real_time_compensation(time: &Time<Virtual>) -> f32 {
+    1.0 / time.relative_speed().max(f32::EPSILON)
+}
+
+fn update_focus(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mut focus: ResMut<Focus>,
+    mut virtual_time: ResMut<Time<Virtual>>,
+    real_time: Res<Time<Real>>,
+    console: Option<Res<Console>>,
+) {
+    // The meter works in real time, otherwise focusing would make itself cheaper
+    let dt = real_time.delta_secs();
+    let typing = !console_closed(console);
+
+    if focus.active {
+        focus.current = (focus.current - focus.drain * dt).max(0.0);
+        if !keyboard.pressed(FOCUS_KEY) || typing || focus.current <= 0.0 {
+            focus.active = false;
+        }
+    } else {
+        focus.current = (focus.current + focus.regen * dt).min(focus.max);
+        if keyboard.just_pressed(FOCUS_KEY)
+            && !typing
+            && focus.current >= MIN_FOCUS_TO_START * focus.max
+        {
+            focus.active = true;
+        }
+    }
+
+    let relative_speed = if focus.active { FOCUS_TIME_SCALE } else { 1.0 };
+    if virtual_time.relative_speed() != relative_speed {
+        virtual_time.set_relative_speed(relative_speed);
+    }
+}
+
+fn spawn_focus_meter(mut commands: Commands, hud: Single<Entity, With<HudRoot>>) {
+    commands.spawn((
+        ChildOf(*hud),
+        Name::new("Focus Meter"),
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(40.0),
+            width: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        Pickable::IGNORE,
+        children![(
+            FocusMeter,
+            Node {
+                width: Val::Px(200.0),
+                height: Val::Px(6.0),
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0.0, 0.0, 0.0, METER_BACKGROUND_ALPHA)),
+            Visibility::Hidden,
+            children![(
+                FocusMeterFill,
+                Node {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    ..default()
+                },
+                BackgroundColor(METER_COLOR),
+            )],
+        )],
+    ));
+}
+
+#[allow(clippy::type_complexity)]
+fn update_focus_meter(
+    focus: Res<Focus>,
+    settings: Res<HudSettings>,
+    mut meter: Single<(&mut Visibility, &mut BackgroundColor), With<FocusMeter>>,
+    mut fill: Single<
+        (&mut Node, &mut BackgroundColor),
+        (With<FocusMeterFill>, Without<FocusMeter>),
+    >,
+) {
+    if !focus.is_changed() && !settings.is_changed() {
+        return;
+    }
+
+    let (visibility, background) = &mut *meter;
+    let full = focus.current >= focus.max && !focus.active;
+    visibility.set_if_neq(if full {
+        Visibility::Hidden
+    } else {
+        Visibility::Inherited
+    });
+    background.set_if_neq(BackgroundColor(Color::srgba(
+        0.0,
+        0.0,
+        0.0,
+        METER_BACKGROUND_ALPHA * settings.alpha(),
+    )));
+
+    let (node, color) = &mut *fill;
+    node.width = Val::Percent(100.0 * focus.current / focus.max.max(f32::EPSILON));
+    color.set_if_neq(BackgroundColor(METER_COLOR.with_alpha(settings.alpha())));
+}
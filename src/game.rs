@@ -1,7 +1,8 @@
 use std::f32::consts::PI;
 
 use avian3d::prelude::*;
-use bevy::light::CascadeShadowConfigBuilder;
+use bevy::input::common_conditions::input_toggle_active;
+use bevy::light::{CascadeShadowConfigBuilder, DirectionalLightShadowMap, NotShadowCaster};
 use bevy::post_process::bloom::Bloom;
 use bevy::post_process::motion_blur::MotionBlur;
 use bevy::{math::Affine2, prelude::*};
@@ -12,49 +13,238 @@ use bevy_tnua::{TnuaNotPlatform, prelude::*};
 use bevy_tnua_avian3d::prelude::*;
 
 use crate::assets::*;
-use crate::camera::ThirdPersonCameraPlugin;
-use crate::chunks::ChunkObserver;
+use crate::camera::{MotionBlurSettings, ThirdPersonCamera, ThirdPersonCameraPlugin};
+use crate::chunks::{ChunkObserver, RenderDistance, TerrainSettings, WorldSeed, terrain_height_at};
+use crate::collection::ItemKind;
+use crate::dungeon::DungeonEntrance;
 use crate::platform::PlatformPath;
-use crate::player::controller::PlayerRoot;
+use crate::player::controller::{GameLayer, PlayerRoot};
 use crate::spawners::*;
 
 pub struct GamePlugin;
 
 #[derive(Component)]
-pub struct Pickupable;
+#[require(CollisionLayers = CollisionLayers::new(GameLayer::Pickup, LayerMask::ALL))]
+pub struct Pickupable(pub ItemKind);
+
+/// Breaks when hit by the sword
+#[derive(Component)]
+pub struct Fragile;
+
+/// What the ground is made of, decides the look of impact particles. Only level geometry has
+/// a surface, so this also puts it in the terrain collision layer.
+#[derive(Component, Clone, Copy, Default, PartialEq, Eq)]
+#[require(CollisionLayers = CollisionLayers::new(GameLayer::Terrain, LayerMask::ALL))]
+pub enum Surface {
+    Grass,
+    #[default]
+    Stone,
+}
+
+/// Glowing obelisk far to the north, so players can orient themselves in the procedural world
+#[derive(Component)]
+pub struct NorthMarker;
+
+/// Which scene is built when the game starts
+#[derive(Resource, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SceneKind {
+    /// The regular scene with stairs, props and rolling terrain
+    #[default]
+    Full,
+    /// Flat ground with a few dummies and spawn points, started with `--arena`
+    TestArena,
+}
+
+/// Marked spot in the test arena to put things down for testing, like with the console
+#[derive(Component)]
+pub struct SpawnPoint;
+
+impl SceneKind {
+    fn from_args() -> Self {
+        if std::env::args().any(|arg| arg == "--arena") {
+            SceneKind::TestArena
+        } else {
+            SceneKind::Full
+        }
+    }
+}
+
+/// Preset trading shadow quality for performance
+#[derive(Resource, Reflect, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[reflect(Resource)]
+pub enum GraphicsQuality {
+    /// Short, low resolution sun shadows and no torch shadows
+    Low,
+    Medium,
+    #[default]
+    High,
+}
+
+impl GraphicsQuality {
+    pub fn torch_shadows(self) -> bool {
+        self != GraphicsQuality::Low
+    }
+}
+
+/// One switch for weak machines: low graphics and particle quality and a short render distance,
+/// without sun shadows, bloom or motion blur. Turning it off restores the settings from before.
+#[derive(Resource, Reflect, Default)]
+#[reflect(Resource)]
+pub struct LowSpecMode {
+    pub enabled: bool,
+    /// Graphics quality, particle quality, motion blur and render distance from before it was
+    /// turned on
+    #[reflect(ignore)]
+    previous: Option<(GraphicsQuality, ParticleQuality, bool, RenderDistance)>,
+}
+
+/// Render distance while in low-spec mode, the chunks right around the player and little more
+const LOW_SPEC_RENDER_DISTANCE: RenderDistance = RenderDistance {
+    load_radius: 1,
+    unload_distance: 4,
+};
+
+/// Horizontal distance of the north marker from the player, it travels along so it can never
+/// be reached or passed
+const NORTH_MARKER_DISTANCE: f32 = 400.0;
 
 impl Plugin for GamePlugin {
     fn build(&self, app: &mut App) {
         app.add_plugins(avian3d::prelude::PhysicsPlugins::default());
         app.insert_resource(avian3d::prelude::Gravity(Vec3::NEG_Y * 9.0));
-        //app.add_plugins(avian3d::prelude::PhysicsDebugPlugin::default());
+
+        // Collider debug rendering, frame stepping and the dev console, only in debug builds.
+        // The console module is always compiled so the game can check whether it is open.
+        #[cfg(debug_assertions)]
+        {
+            app.add_plugins(avian3d::prelude::PhysicsDebugPlugin::default());
+            // For the chunk debug view, line polygon mode isn't supported on the web
+            #[cfg(not(target_arch = "wasm32"))]
+            app.add_plugins(bevy::pbr::wireframe::WireframePlugin::default());
+            app.add_systems(Startup, hide_physics_debug);
+            app.add_systems(Update, (toggle_physics_debug, pause_and_step_time));
+            app.add_plugins(crate::console::ConsolePlugin);
+        }
+
         app.add_plugins(TnuaControllerPlugin::new(FixedUpdate));
         app.add_plugins(TnuaAvian3dPlugin::new(FixedUpdate));
         app.add_plugins(EguiPlugin::default());
 
+        // Hidden until toggled, so it doesn't grab the mouse and keyboard while playing
         #[cfg(not(target_arch = "wasm32"))]
-        app.add_plugins(WorldInspectorPlugin::new());
+        app.add_plugins(
+            WorldInspectorPlugin::new().run_if(input_toggle_active(false, INSPECTOR_KEY)),
+        );
 
         app.add_plugins(HanabiPlugin);
         app.add_plugins(crate::assets::AssetPlugin);
+        app.add_plugins(crate::loading::LoadingPlugin);
         app.add_plugins(crate::spawners::SpawnPlugin);
         app.add_plugins(crate::player::PlayerPlugin);
         app.add_plugins(crate::platform::PlatformPlugin);
         app.add_plugins(crate::chunks::ChunksPlugin);
+        app.add_plugins(crate::hud::HudPlugin);
+        app.add_plugins(crate::examine::ExaminePlugin);
+        app.add_plugins(crate::focus::FocusPlugin);
+        app.add_plugins(crate::help::HelpPlugin);
+        app.add_plugins(crate::collection::CollectionPlugin);
+        app.add_plugins(crate::dungeon::DungeonPlugin);
         app.add_plugins(ThirdPersonCameraPlugin);
         app.insert_resource(ClearColor(Color::srgb(0.08, 0.02, 0.02))); // Very dark black background
-        app.add_systems(OnEnter(MyStates::Next), setup);
+        let scene = SceneKind::from_args();
+        app.insert_resource(scene);
+        if scene == SceneKind::TestArena {
+            app.insert_resource(TerrainSettings {
+                height_scale: 0.0,
+                ..default()
+            });
+        }
+        app.add_systems(
+            OnEnter(MyStates::Next),
+            (
+                setup,
+                spawn_props.run_if(resource_equals(SceneKind::Full)),
+                spawn_test_arena.run_if(resource_equals(SceneKind::TestArena)),
+            ),
+        );
+        app.register_type::<GraphicsQuality>();
+        // The web build is usually running on weaker hardware
+        app.insert_resource(if cfg!(target_arch = "wasm32") {
+            GraphicsQuality::Medium
+        } else {
+            GraphicsQuality::High
+        });
+        app.add_systems(
+            Update,
+            apply_graphics_quality
+                .run_if(in_state(MyStates::Next).and(resource_changed::<GraphicsQuality>)),
+        );
+        app.register_type::<LowSpecMode>();
+        app.init_resource::<LowSpecMode>();
+        app.add_systems(
+            Update,
+            apply_low_spec_mode
+                .run_if(in_state(MyStates::Next).and(resource_changed::<LowSpecMode>)),
+        );
+        app.add_systems(
+            Update,
+            keep_north_marker_on_horizon
+                .run_if(in_state(MyStates::Next).and(resource_equals(SceneKind::Full))),
+        );
     }
 }
 
-/// set up a simple 3D scene
-fn setup(
-    mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-    mut ambient_light: ResMut<AmbientLight>,
-    assets: Res<GameAssets>,
+pub const INSPECTOR_KEY: KeyCode = KeyCode::F2;
+
+#[cfg(debug_assertions)]
+pub const PHYSICS_DEBUG_KEY: KeyCode = KeyCode::F3;
+
+#[cfg(debug_assertions)]
+fn hide_physics_debug(mut store: ResMut<GizmoConfigStore>) {
+    store.config_mut::<PhysicsGizmos>().0.enabled = false;
+}
+
+/// Gizmos are redrawn every frame, so disabling them leaves nothing behind
+#[cfg(debug_assertions)]
+fn toggle_physics_debug(keyboard: Res<ButtonInput<KeyCode>>, mut store: ResMut<GizmoConfigStore>) {
+    if keyboard.just_pressed(PHYSICS_DEBUG_KEY) {
+        let (config, _) = store.config_mut::<PhysicsGizmos>();
+        config.enabled = !config.enabled;
+    }
+}
+
+#[cfg(debug_assertions)]
+pub const PAUSE_KEY: KeyCode = KeyCode::F4;
+#[cfg(debug_assertions)]
+pub const STEP_KEY: KeyCode = KeyCode::F5;
+
+/// Freeze the game and advance it one fixed tick at a time, to see exactly what the character
+/// controller and platforms do. Pausing also shows the colliders.
+#[cfg(debug_assertions)]
+fn pause_and_step_time(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mut virtual_time: ResMut<Time<Virtual>>,
+    mut fixed_time: ResMut<Time<Fixed>>,
+    mut store: ResMut<GizmoConfigStore>,
 ) {
+    if keyboard.just_pressed(PAUSE_KEY) {
+        if virtual_time.is_paused() {
+            virtual_time.unpause();
+        } else {
+            virtual_time.pause();
+            store.config_mut::<PhysicsGizmos>().0.enabled = true;
+        }
+    }
+
+    // Paused virtual time adds nothing to the fixed timestep, so this runs exactly one tick
+    if virtual_time.is_paused() && keyboard.just_pressed(STEP_KEY) {
+        let timestep = fixed_time.timestep();
+        fixed_time.accumulate_overstep(timestep);
+    }
+}
+
+/// set up a simple 3D scene
+fn setup(mut commands: Commands, mut ambient_light: ResMut<AmbientLight>) {
     ambient_light.brightness = 100.0;
 
     commands.spawn((
@@ -79,6 +269,56 @@ fn setup(
         .build(),
     ));
 
+    // Player-following camera
+    let mut camera_entity = commands.spawn((
+        Camera3d::default(),
+        crate::camera::ThirdPersonCamera::default(),
+        Transform::from_xyz(0.0, 3.0, 5.0).looking_at(Vec3::new(0.0, 1.0, 0.0), Vec3::Y),
+        Bloom::NATURAL,
+    ));
+
+    camera_entity.insert(MotionBlur {
+        shutter_angle: 1.25,
+        samples: 2,
+    });
+
+    commands.spawn((PlayerRoot, Name::new("Player"), ChunkObserver));
+}
+
+/// Torches, backdrop, platform, stairs and props of the regular scene
+fn spawn_props(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    assets: Res<GameAssets>,
+    terrain: Res<TerrainSettings>,
+    seed: Res<WorldSeed>,
+) {
+    commands.spawn((SpawnTorch, Transform::from_xyz(-2.0, 1.0, 0.0)));
+
+    commands.spawn((SpawnTorch, Transform::from_xyz(2.0, 1.0, 0.0)));
+
+    commands.spawn((ParticleEffect::new(assets.void.clone()), VoidEffect));
+
+    commands.spawn((
+        NorthMarker,
+        Name::new("North Marker"),
+        Mesh3d(meshes.add(Cuboid::new(6.0, 120.0, 6.0))),
+        MeshMaterial3d(materials.add(StandardMaterial {
+            base_color: Color::srgb(0.6, 0.8, 1.0),
+            // Lit, an unlit material ignores the emissive and wouldn't bloom
+            emissive: LinearRgba::rgb(4.0, 6.0, 12.0),
+            ..default()
+        })),
+        Transform::from_translation(Vec3::NEG_Z * NORTH_MARKER_DISTANCE),
+        NotShadowCaster,
+    ));
+
+    commands.spawn((
+        DungeonEntrance,
+        Transform::from_xyz(8.0, terrain_height_at(8.0, -8.0, &terrain, seed.0), -8.0),
+    ));
+
     commands.spawn((
         Mesh3d(meshes.add(Cuboid::new(2.0, 0.5, 2.0))),
         MeshMaterial3d(materials.add(StandardMaterial {
@@ -95,6 +335,7 @@ fn setup(
         })),
         RigidBody::Kinematic,
         Collider::cuboid(2.0, 0.5, 2.0),
+        Surface::Stone,
         Name::new("Platform"),
         Transform::from_xyz(0.0, 1.0, 10.0),
         PlatformPath {
@@ -119,6 +360,7 @@ fn setup(
                 .with_scale(Vec3::new(0.5, 0.25, 0.5)),
             Name::new("Stairs"),
             RigidBody::Static,
+            Surface::Stone,
             //ColliderConstructor::TrimeshFromMesh,
             ColliderConstructor::ConvexHullFromMesh,
         ));
@@ -128,7 +370,8 @@ fn setup(
     for y in 0..=WIDTH {
         for x in 0..=y {
             commands.spawn((
-                Pickupable,
+                Pickupable(ItemKind::Wineglass),
+                Fragile,
                 Mesh3d(assets.wineglass.clone()),
                 MeshMaterial3d(assets.wineglass_material.clone()),
                 Transform::from_xyz(
@@ -152,7 +395,8 @@ fn setup(
     }
     for x in 0..40 {
         commands.spawn((
-            Pickupable,
+            Pickupable(ItemKind::Wineglass),
+            Fragile,
             Mesh3d(assets.wineglass.clone()),
             MeshMaterial3d(assets.wineglass_material.clone()),
             Transform::from_xyz(0.3 * x as f32, 1.32, -2.0).with_scale(Vec3::splat(0.1)),
@@ -173,7 +417,7 @@ fn setup(
         MeshMaterial3d(assets.trophy_material.clone()),
         Transform::from_xyz(0.0, 4.0, 4.0).with_scale(Vec3::splat(0.1)),
         Name::new("Trophy"),
-        Pickupable,
+        Pickupable(ItemKind::Trophy),
         Mass(0.5),
         RigidBody::Dynamic,
         TnuaNotPlatform,
@@ -189,7 +433,7 @@ fn setup(
         MeshMaterial3d(assets.bong_material.clone()),
         Transform::from_xyz(2.0, 4.0, 4.0).with_scale(Vec3::splat(0.3)),
         Name::new("Bong"),
-        Pickupable,
+        Pickupable(ItemKind::Bong),
         Mass(0.5),
         RigidBody::Dynamic,
         TnuaNotPlatform,
@@ -199,25 +443,131 @@ fn setup(
             z_length: 2.5,
         },
     ));
+}
 
-    // Player-following camera
-    let mut camera_entity = commands.spawn((
-        Camera3d::default(),
-        crate::camera::ThirdPersonCamera::default(),
-        Transform::from_xyz(0.0, 3.0, 5.0).looking_at(Vec3::new(0.0, 1.0, 0.0), Vec3::Y),
-        Bloom::NATURAL,
-    ));
+/// A few training dummies on flat ground, to try out movement and combat without clutter
+/// Dummies to hit and a couple of spawn points, nothing else
+fn spawn_test_arena(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let pad = meshes.add(Cylinder::new(0.6, 0.02));
+    let pad_material = materials.add(StandardMaterial {
+        base_color: Color::srgb(0.3, 0.9, 0.4),
+        unlit: true,
+        ..default()
+    });
+    for x in [-4.0, 4.0] {
+        commands.spawn((
+            SpawnPoint,
+            Name::new("Spawn Point"),
+            Mesh3d(pad.clone()),
+            MeshMaterial3d(pad_material.clone()),
+            Transform::from_xyz(x, 0.01, 4.0),
+            NotShadowCaster,
+        ));
+    }
 
-    camera_entity.insert(MotionBlur {
-        shutter_angle: 1.25,
-        samples: 2,
+    let mesh = meshes.add(Capsule3d::new(0.3, 1.0));
+    let material = materials.add(StandardMaterial {
+        base_color: Color::srgb(0.7, 0.55, 0.35),
+        perceptual_roughness: 1.0,
+        ..default()
     });
 
-    commands.spawn((PlayerRoot, Name::new("Player"), ChunkObserver));
+    for x in -1..=1 {
+        commands.spawn((
+            Mesh3d(mesh.clone()),
+            MeshMaterial3d(material.clone()),
+            Transform::from_xyz(2.0 * x as f32, 1.0, -4.0),
+            Name::new("Dummy"),
+            Mass(30.0),
+            RigidBody::Dynamic,
+            LockedAxes::ROTATION_LOCKED,
+            TnuaNotPlatform,
+            Collider::capsule(0.3, 1.0),
+        ));
+    }
+}
 
-    commands.spawn((SpawnTorch, Transform::from_xyz(-2.0, 1.0, 0.0)));
+fn keep_north_marker_on_horizon(
+    player: Single<&Transform, (With<PlayerRoot>, Without<NorthMarker>)>,
+    mut marker: Single<&mut Transform, With<NorthMarker>>,
+) {
+    // North is -Z, keep the marker level with the player so it always sits on the horizon
+    marker.translation = player.translation + Vec3::NEG_Z * NORTH_MARKER_DISTANCE;
+}
 
-    commands.spawn((SpawnTorch, Transform::from_xyz(2.0, 1.0, 0.0)));
+fn apply_graphics_quality(
+    mut commands: Commands,
+    quality: Res<GraphicsQuality>,
+    sun: Query<Entity, With<DirectionalLight>>,
+    mut torches: Query<&mut PointLight, With<Torch>>,
+) {
+    let (maximum_distance, shadow_map_size) = match *quality {
+        GraphicsQuality::Low => (30.0, 512),
+        GraphicsQuality::Medium => (60.0, 1024),
+        GraphicsQuality::High => (100.0, 2048),
+    };
 
-    commands.spawn((ParticleEffect::new(assets.void.clone()),));
+    commands.insert_resource(DirectionalLightShadowMap {
+        size: shadow_map_size,
+    });
+
+    for entity in sun.iter() {
+        commands.entity(entity).insert(
+            CascadeShadowConfigBuilder {
+                first_cascade_far_bound: 4.0,
+                maximum_distance,
+                ..default()
+            }
+            .build(),
+        );
+    }
+
+    for mut light in torches.iter_mut() {
+        light.shadows_enabled = quality.torch_shadows();
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn apply_low_spec_mode(
+    mut commands: Commands,
+    mut low_spec: ResMut<LowSpecMode>,
+    mut graphics: ResMut<GraphicsQuality>,
+    mut particles: ResMut<ParticleQuality>,
+    mut motion_blur: ResMut<MotionBlurSettings>,
+    mut render_distance: ResMut<RenderDistance>,
+    mut sun: Query<&mut DirectionalLight>,
+    camera: Single<Entity, With<ThirdPersonCamera>>,
+) {
+    // Remembering the previous settings shouldn't count as a change of the mode itself
+    let low_spec = low_spec.bypass_change_detection();
+
+    if low_spec.enabled {
+        if low_spec.previous.is_none() {
+            low_spec.previous =
+                Some((*graphics, *particles, motion_blur.enabled, *render_distance));
+        }
+        graphics.set_if_neq(GraphicsQuality::Low);
+        particles.set_if_neq(ParticleQuality::Low);
+        render_distance.set_if_neq(LOW_SPEC_RENDER_DISTANCE);
+        motion_blur.enabled = false;
+        commands.entity(*camera).remove::<Bloom>();
+    } else {
+        if let Some((graphics_quality, particle_quality, motion_blur_enabled, distance)) =
+            low_spec.previous.take()
+        {
+            graphics.set_if_neq(graphics_quality);
+            particles.set_if_neq(particle_quality);
+            motion_blur.enabled = motion_blur_enabled;
+            render_distance.set_if_neq(distance);
+        }
+        commands.entity(*camera).insert(Bloom::NATURAL);
+    }
+
+    for mut light in sun.iter_mut() {
+        light.shadows_enabled = !low_spec.enabled;
+    }
 }
@@ -0,0 +1,162 @@
+use bevy::prelude::*;
+
+use crate::assets::MyStates;
+#[cfg(debug_assertions)]
+use crate::camera::FREE_CAMERA_KEY;
+use crate::collection::COLLECTION_KEY;
+#[cfg(debug_assertions)]
+use crate::console::CONSOLE_KEY;
+use crate::examine::EXAMINE_KEY;
+use crate::focus::FOCUS_KEY;
+use crate::game::INSPECTOR_KEY;
+#[cfg(debug_assertions)]
+use crate::game::{PAUSE_KEY, PHYSICS_DEBUG_KEY, STEP_KEY};
+use crate::hud::HUD_KEY;
+use crate::player::controller::ROLL_KEY;
+use crate::player::grab::GRAB_KEY;
+
+/// Toggleable overlay listing the controls
+pub struct HelpPlugin;
+
+const HELP_KEY: KeyCode = KeyCode::F1;
+
+#[derive(Component)]
+struct HelpOverlay;
+
+impl Plugin for HelpPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(MyStates::Next), spawn_help_overlay);
+        app.add_systems(Update, toggle_help_overlay.run_if(in_state(MyStates::Next)));
+    }
+}
+
+/// Readable name for a key, `KeyE` becomes `E` and `ShiftLeft` stays as is
+fn key_name(key: KeyCode) -> String {
+    let name = format!("{key:?}");
+    match name.strip_prefix("Key").or(name.strip_prefix("Digit")) {
+        Some(short) => short.to_string(),
+        None => name,
+    }
+}
+
+/// The bindings by category, only listing the debug tools that are compiled in
+fn bindings() -> Vec<(&'static str, Vec<(String, &'static str)>)> {
+    let mut ui = vec![
+        ("Left click".to_string(), "Capture mouse"),
+        (key_name(KeyCode::Escape), "Release mouse"),
+        (key_name(EXAMINE_KEY), "Examine surroundings (hold)"),
+        (key_name(COLLECTION_KEY), "Show collection (hold)"),
+        (key_name(HELP_KEY), "Toggle this help"),
+        (key_name(HUD_KEY), "Toggle HUD"),
+    ];
+    // The inspector isn't available on the web
+    if cfg!(not(target_arch = "wasm32")) {
+        ui.push((key_name(INSPECTOR_KEY), "Toggle world inspector"));
+    }
+
+    #[allow(unused_mut)]
+    let mut categories = vec![
+        (
+            "Movement",
+            vec![
+                ("W A S D".to_string(), "Move"),
+                (key_name(KeyCode::ShiftLeft), "Sprint"),
+                (key_name(KeyCode::Space), "Jump"),
+                (key_name(ROLL_KEY), "Dodge roll"),
+            ],
+        ),
+        (
+            "Combat",
+            vec![
+                (key_name(KeyCode::KeyV), "Attack"),
+                (key_name(KeyCode::KeyO), "Drop kick"),
+                (key_name(FOCUS_KEY), "Focus (hold)"),
+                (key_name(GRAB_KEY), "Grab, release to throw"),
+            ],
+        ),
+        ("UI", ui),
+    ];
+
+    #[cfg(debug_assertions)]
+    categories.push((
+        "Debug",
+        vec![
+            (key_name(PHYSICS_DEBUG_KEY), "Toggle collider debug view"),
+            (key_name(PAUSE_KEY), "Pause or resume time"),
+            (key_name(STEP_KEY), "Step one physics tick while paused"),
+            (key_name(FREE_CAMERA_KEY), "Toggle free camera"),
+            (key_name(CONSOLE_KEY), "Toggle console"),
+        ],
+    ));
+
+    categories
+}
+
+fn spawn_help_overlay(mut commands: Commands) {
+    commands
+        .spawn((
+            HelpOverlay,
+            Name::new("Help Overlay"),
+            Node {
+                position_type: PositionType::Absolute,
+                top: Val::Px(20.0),
+                right: Val::Px(20.0),
+                padding: UiRect::all(Val::Px(12.0)),
+                flex_direction: FlexDirection::Column,
+                row_gap: Val::Px(4.0),
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.6)),
+            // Purely informational, clicks go through to the game
+            Pickable::IGNORE,
+            Visibility::Hidden,
+        ))
+        .with_children(|parent| {
+            for (category, keys) in bindings() {
+                parent.spawn((
+                    Text::new(category),
+                    TextFont {
+                        font_size: 16.0,
+                        ..default()
+                    },
+                    TextColor(Color::srgb(1.0, 0.8, 0.4)),
+                    Node {
+                        margin: UiRect::top(Val::Px(6.0)),
+                        ..default()
+                    },
+                ));
+
+                for (key, action) in keys {
+                    let font = TextFont {
+                        font_size: 14.0,
+                        ..default()
+                    };
+                    let color = TextColor(Color::srgb(1.0, 0.95, 0.8));
+                    parent.spawn((
+                        Node::default(),
+                        children![
+                            (
+                                Text::new(key),
+                                font.clone(),
+                                color,
+                                Node {
+                                    width: Val::Px(90.0),
+                                    ..default()
+                                },
+                            ),
+                            (Text::new(action), font, color),
+                        ],
+                    ));
+                }
+            }
+        });
+}
+
+fn toggle_help_overlay(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mut overlay: Single<&mut Visibility, With<HelpOverlay>>,
+) {
+    if keyboard.just_pressed(HELP_KEY) {
+        overlay.toggle_visible_hidden();
+    }
+}
@@ -0,0 +1,283 @@
+use avian3d::prelude::LinearVelocity;
+use bevy::prelude::*;
+use bevy::window::{PrimaryWindow, WindowResized};
+
+use crate::assets::MyStates;
+use crate::console::console_closed;
+use crate::player::controller::PlayerRoot;
+
+/// Plugin for the heads-up display
+pub struct HudPlugin;
+
+/// Window size the HUD pixel sizes are designed for
+const REFERENCE_RESOLUTION: Vec2 = Vec2::new(1920.0, 1080.0);
+const MIN_UI_SCALE: f32 = 0.5;
+const MAX_UI_SCALE: f32 = 3.0;
+
+/// Hide the HUD, for a clean screen when taking screenshots
+pub const HUD_KEY: KeyCode = KeyCode::KeyH;
+
+/// User tweakable HUD settings
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct HudSettings {
+    /// Extra multiplier on top of the window-size based scale
+    pub scale: f32,
+    /// Whether the HUD is shown at all, toggled with the HUD key
+    pub visible: bool,
+    /// Multiplier on the alpha of all HUD elements
+    pub opacity: f32,
+}
+
+impl Default for HudSettings {
+    fn default() -> Self {
+        Self {
+            scale: 1.0,
+            visible: true,
+            opacity: 1.0,
+        }
+    }
+}
+
+impl HudSettings {
+    /// Alpha multiplier for HUD colors
+    pub fn alpha(&self) -> f32 {
+        self.opacity.clamp(0.0, 1.0)
+    }
+}
+
+/// Full screen node the HUD elements are spawned under, hiding it hides the whole HUD
+#[derive(Component)]
+pub struct HudRoot;
+
+/// Horizontal speed at which the crosshair is spread the furthest (sprinting)
+const CROSSHAIR_FULL_SPREAD_SPEED: f32 = 5.4;
+const CROSSHAIR_SPREAD_SMOOTHING: f32 = 10.0;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Reflect)]
+pub enum CrosshairStyle {
+    #[default]
+    Cross,
+    Dot,
+    CrossWithDot,
+    Hidden,
+}
+
+/// Look of the crosshair in the middle of the screen
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct CrosshairSettings {
+    pub style: CrosshairStyle,
+    /// Length of each arm
+    pub size: f32,
+    pub thickness: f32,
+    /// Distance from the center to the arms while standing still
+    pub gap: f32,
+    /// Extra gap added while moving at full speed
+    pub max_spread: f32,
+    pub color: Color,
+}
+
+impl Default for CrosshairSettings {
+    fn default() -> Self {
+        Self {
+            style: CrosshairStyle::default(),
+            size: 8.0,
+            thickness: 2.0,
+            gap: 4.0,
+            max_spread: 10.0,
+            color: Color::srgba(1.0, 0.95, 0.8, 0.8),
+        }
+    }
+}
+
+/// One of the four crosshair arms, pointing in this direction away from the center
+#[derive(Component)]
+struct CrosshairArm(Vec2);
+
+#[derive(Component)]
+struct CrosshairDot;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<HudSettings>();
+        app.init_resource::<HudSettings>();
+        app.register_type::<CrosshairSettings>();
+        app.init_resource::<CrosshairSettings>();
+        app.add_systems(Update, scale_ui_to_window);
+        app.add_systems(
+            OnEnter(MyStates::Next),
+            (spawn_hud_root, spawn_crosshair).chain(),
+        );
+        app.add_systems(
+            Update,
+            (
+                toggle_hud.run_if(console_closed),
+                apply_hud_visibility.run_if(resource_changed::<HudSettings>),
+                update_crosshair,
+            )
+                .chain()
+                .run_if(in_state(MyStates::Next)),
+        );
+    }
+}
+
+/// Scale all `Val::Px` sizes and fonts uniformly with the window, so the HUD keeps its
+/// proportions (and circles stay circular) on both tiny and 4K windows.
+fn scale_ui_to_window(
+    window: Single<&Window, With<PrimaryWindow>>,
+    mut resized: MessageReader<WindowResized>,
+    settings: Res<HudSettings>,
+    mut ui_scale: ResMut<UiScale>,
+) {
+    if resized.read().count() == 0 && !settings.is_changed() {
+        return;
+    }
+
+    let fit = (window.size() / REFERENCE_RESOLUTION).min_element();
+    ui_scale.0 = (fit * settings.scale).clamp(MIN_UI_SCALE, MAX_UI_SCALE);
+}
+
+pub fn spawn_hud_root(mut commands: Commands) {
+    commands.spawn((
+        HudRoot,
+        Name::new("HUD"),
+        Node {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            ..default()
+        },
+        Pickable::IGNORE,
+    ));
+}
+
+fn spawn_crosshair(mut commands: Commands, hud: Single<Entity, With<HudRoot>>) {
+    commands
+        .spawn((
+            ChildOf(*hud),
+            Name::new("Crosshair"),
+            Node {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            Pickable::IGNORE,
+        ))
+        .with_children(|parent| {
+            // Zero sized anchor in the middle of the screen, the parts are placed around it
+            parent.spawn(Node::default()).with_children(|anchor| {
+                for direction in [Vec2::X, Vec2::NEG_X, Vec2::Y, Vec2::NEG_Y] {
+                    anchor.spawn((
+                        CrosshairArm(direction),
+                        Node {
+                            position_type: PositionType::Absolute,
+                            ..default()
+                        },
+                        BackgroundColor::default(),
+                    ));
+                }
+                anchor.spawn((
+                    CrosshairDot,
+                    Node {
+                        position_type: PositionType::Absolute,
+                        ..default()
+                    },
+                    BackgroundColor::default(),
+                ));
+            });
+        });
+}
+
+fn toggle_hud(keyboard: Res<ButtonInput<KeyCode>>, mut settings: ResMut<HudSettings>) {
+    if keyboard.just_pressed(HUD_KEY) {
+        settings.visible = !settings.visible;
+    }
+}
+
+/// Also picks up the setting being changed from the inspector
+fn apply_hud_visibility(
+    settings: Res<HudSettings>,
+    mut root: Single<&mut Visibility, With<HudRoot>>,
+) {
+    root.set_if_neq(if settings.visible {
+        Visibility::Inherited
+    } else {
+        Visibility::Hidden
+    });
+}
+
+/// Widen the crosshair while the player moves and tighten it again when standing still
+#[allow(clippy::type_complexity)]
+fn update_crosshair(
+    settings: Res<CrosshairSettings>,
+    hud: Res<HudSettings>,
+    player: Single<&LinearVelocity, With<PlayerRoot>>,
+    mut arms: Query<
+        (
+            &CrosshairArm,
+            &mut Node,
+            &mut BackgroundColor,
+            &mut Visibility,
+        ),
+        Without<CrosshairDot>,
+    >,
+    mut dot: Single<(&mut Node, &mut BackgroundColor, &mut Visibility), With<CrosshairDot>>,
+    time: Res<Time<Real>>,
+    mut spread: Local<f32>,
+) {
+    let movement = (player.xz().length() / CROSSHAIR_FULL_SPREAD_SPEED).min(1.0);
+    *spread = spread.lerp(
+        movement * settings.max_spread,
+        1.0 - (-time.delta_secs() * CROSSHAIR_SPREAD_SMOOTHING).exp(),
+    );
+
+    let show_arms = matches!(
+        settings.style,
+        CrosshairStyle::Cross | CrosshairStyle::CrossWithDot
+    );
+    let show_dot = matches!(
+        settings.style,
+        CrosshairStyle::Dot | CrosshairStyle::CrossWithDot
+    );
+    let tint = settings
+        .color
+        .with_alpha(settings.color.alpha() * hud.alpha());
+
+    for (CrosshairArm(direction), mut node, mut color, mut visibility) in arms.iter_mut() {
+        let size = if direction.x != 0.0 {
+            Vec2::new(settings.size, settings.thickness)
+        } else {
+            Vec2::new(settings.thickness, settings.size)
+        };
+        let center = direction * (settings.gap + *spread + settings.size / 2.0);
+        let top_left = center - size / 2.0;
+
+        node.left = Val::Px(top_left.x);
+        node.top = Val::Px(top_left.y);
+        node.width = Val::Px(size.x);
+        node.height = Val::Px(size.y);
+        color.0 = tint;
+        *visibility = if show_arms {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+    }
+
+    let (node, color, visibility) = &mut *dot;
+    let dot_size = settings.thickness * 1.5;
+    node.left = Val::Px(-dot_size / 2.0);
+    node.top = Val::Px(-dot_size / 2.0);
+    node.width = Val::Px(dot_size);
+    node.height = Val::Px(dot_size);
+    color.0 = tint;
+    **visibility = if show_dot {
+        Visibility::Inherited
+    } else {
+        Visibility::Hidden
+    };
+}
@@ -2,7 +2,15 @@ pub mod animations_utils;
 pub mod assets;
 pub mod camera;
 pub mod chunks;
+pub mod collection;
+pub mod console;
+pub mod dungeon;
+pub mod examine;
+pub mod focus;
 pub mod game;
+pub mod help;
+pub mod hud;
+pub mod loading;
 pub mod platform;
 pub mod player;
 pub mod spawners;
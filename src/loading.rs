@@ -0,0 +1,154 @@
+use bevy::prelude::*;
+use bevy_asset_loader::prelude::*;
+use iyes_progress::prelude::*;
+
+use crate::assets::MyStates;
+
+/// Loading screen with a progress bar while the assets load, and a fade from black once the game
+/// starts
+pub struct LoadingPlugin;
+
+const FADE_IN_DURATION: f32 = 1.0;
+
+#[derive(Component)]
+struct LoadingScreen;
+
+#[derive(Component)]
+struct LoadingText;
+
+/// The filled part of the progress bar
+#[derive(Component)]
+struct LoadingBar;
+
+#[derive(Component)]
+struct FadeIn(Timer);
+
+impl Plugin for LoadingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(MyStates::AssetLoading), spawn_loading_screen);
+        app.add_systems(
+            Update,
+            show_loading_progress
+                .run_if(in_state(MyStates::AssetLoading))
+                .after(LoadingStateSet(MyStates::AssetLoading)),
+        );
+        app.add_systems(OnEnter(MyStates::AssetPreparing), show_preparing);
+        app.add_systems(OnExit(MyStates::AssetPreparing), despawn_loading_screen);
+        app.add_systems(OnEnter(MyStates::Next), spawn_fade_in);
+        app.add_systems(Update, fade_in.run_if(in_state(MyStates::Next)));
+    }
+}
+
+fn spawn_loading_screen(mut commands: Commands) {
+    // The game camera only exists once the scene is set up
+    commands.spawn((LoadingScreen, Camera2d));
+
+    commands.spawn((
+        LoadingScreen,
+        Name::new("Loading Screen"),
+        Node {
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            flex_direction: FlexDirection::Column,
+            row_gap: Val::Px(16.0),
+            ..default()
+        },
+        BackgroundColor(Color::BLACK),
+        children![
+            (
+                LoadingText,
+                Text::new("Loading"),
+                TextFont {
+                    font_size: 32.0,
+                    ..default()
+                },
+                TextColor(Color::srgb(1.0, 0.95, 0.8)),
+            ),
+            (
+                Node {
+                    width: Val::Px(320.0),
+                    height: Val::Px(8.0),
+                    ..default()
+                },
+                BackgroundColor(Color::srgb(0.15, 0.15, 0.15)),
+                children![(
+                    LoadingBar,
+                    Node {
+                        width: Val::Percent(0.0),
+                        height: Val::Percent(100.0),
+                        ..default()
+                    },
+                    BackgroundColor(Color::srgb(1.0, 0.95, 0.8)),
+                )],
+            ),
+        ],
+    ));
+}
+
+fn show_loading_progress(
+    progress: Option<Res<ProgressTracker<MyStates>>>,
+    mut text: Single<&mut Text, With<LoadingText>>,
+    mut bar: Single<&mut Node, With<LoadingBar>>,
+) {
+    let Some(progress) = progress.map(|tracker| tracker.get_global_progress()) else {
+        return;
+    };
+
+    // Nothing is registered in the very first frame
+    let fraction = if progress.total > 0 {
+        progress.done as f32 / progress.total as f32
+    } else {
+        0.0
+    };
+    text.0 = format!("Loading {}/{}", progress.done, progress.total);
+    bar.width = Val::Percent(fraction * 100.0);
+}
+
+/// Everything is loaded, the textures and particle effects are set up next
+fn show_preparing(
+    mut text: Single<&mut Text, With<LoadingText>>,
+    mut bar: Single<&mut Node, With<LoadingBar>>,
+) {
+    text.0 = "Preparing".to_string();
+    bar.width = Val::Percent(100.0);
+}
+
+fn despawn_loading_screen(mut commands: Commands, q: Query<Entity, With<LoadingScreen>>) {
+    for entity in q.iter() {
+        commands.entity(entity).despawn();
+    }
+}
+
+fn spawn_fade_in(mut commands: Commands) {
+    commands.spawn((
+        FadeIn(Timer::from_seconds(FADE_IN_DURATION, TimerMode::Once)),
+        Name::new("Fade In"),
+        Node {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            ..default()
+        },
+        BackgroundColor(Color::BLACK),
+        // Drawn on top of the rest of the UI, without catching clicks
+        GlobalZIndex(i32::MAX),
+        Pickable::IGNORE,
+    ));
+}
+
+fn fade_in(
+    mut commands: Commands,
+    mut q: Query<(Entity, &mut FadeIn, &mut BackgroundColor)>,
+    time: Res<Time<Real>>,
+) {
+    for (entity, mut fade, mut color) in q.iter_mut() {
+        fade.0.tick(time.delta());
+        color.0.set_alpha(1.0 - fade.0.fraction());
+
+        if fade.0.is_finished() {
+            commands.entity(entity).despawn();
+        }
+    }
+}
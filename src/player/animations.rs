@@ -206,6 +206,29 @@ pub fn animations_from_controller(
                     ..default()
                 }
             }
+            Rolling(..) => {
+                // No roll clip, the crouch of the landing reads as ducking into the roll
+                if state_transioned {
+                    player
+                        .start(clips.landing)
+                        .set_seek_time(0.0)
+                        .set_speed(1.5);
+                }
+                *weights = AnimationWeights {
+                    landing: 1.0,
+                    ..default()
+                }
+            }
+            Mantling(..) => {
+                // No climbing clip, the push off of the jump reads as pulling up
+                if state_transioned {
+                    player.start(clips.jump).set_seek_time(0.3).set_speed(1.5);
+                }
+                *weights = AnimationWeights {
+                    jump: 1.0,
+                    ..default()
+                }
+            }
         }
 
         *prev_state = state.clone();
@@ -6,28 +6,46 @@ use bevy::{platform::collections::HashSet, prelude::*};
 use bevy_tnua::{builtins::TnuaBuiltinJumpState, prelude::*};
 use bevy_tnua_avian3d::prelude::*;
 
-use crate::assets::GameAssets;
+use crate::assets::{GameAssets, ParticleSeeds};
+use crate::camera::{FreeCamera, ThirdPersonCamera};
+use crate::chunks::{TerrainSettings, WorldSeed, terrain_height_at};
+use crate::focus::real_time_compensation;
 use bevy_hanabi::prelude::*;
 
+use crate::collection::ItemCollected;
+use crate::console::{Console, console_closed};
 use crate::game::Pickupable;
+use crate::player::grab::Held;
+use crate::player::weapon::{Sword, WeaponDrawn, WeaponSocket, sword_transform};
 
 #[derive(Component, Default)]
 #[require(Transform, InheritedVisibility)]
 pub struct PlayerRoot;
 
 #[derive(PhysicsLayer, Default)]
-enum GameLayer {
+pub enum GameLayer {
     #[default]
     Default,
     Player,
+    /// The static and moving level geometry, like the terrain, stairs and platforms
+    Terrain,
+    /// Small props that can be picked up or carried
+    Pickup,
 }
 
-fn all_except_player() -> LayerMask {
+pub fn all_except_player() -> LayerMask {
     let mut x = LayerMask::ALL;
     x &= !GameLayer::Player.to_bits();
     x
 }
 
+/// Things that block the view, small props are ignored so a wineglass doesn't yank the camera
+pub fn view_blockers() -> LayerMask {
+    let mut x = all_except_player();
+    x &= !GameLayer::Pickup.to_bits();
+    x
+}
+
 #[derive(Component, Default, Debug)]
 pub struct ControllerSensors {
     pub actual_velocity: Vec3,
@@ -47,19 +65,36 @@ pub enum ControllerState {
     Falling,
     DropKicking(Timer, Timer),
     Attacking(Timer),
+    /// Climbing onto a ledge, from the start to the end position
+    Mantling(Timer, Vec3, Vec3),
+    /// Dodge rolling in a direction along the ground
+    Rolling(Timer, Vec3),
 }
 
 #[derive(Component)]
 pub struct FootRayCaster;
 
-pub fn on_player_spawn(on: On<Add, PlayerRoot>, mut commands: Commands, assets: Res<GameAssets>) {
+pub fn on_player_spawn(
+    on: On<Add, PlayerRoot>,
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+    terrain: Res<TerrainSettings>,
+    seed: Res<WorldSeed>,
+) {
+    // Spawn on top of the terrain, which may not be loaded yet so sample the noise directly.
+    // The center floats 0.85 above the ground, plus a little margin to drop onto it.
+    let ground = terrain_height_at(0.0, 0.0, &terrain, seed.0);
+
     commands.entity(on.event_target()).insert((
-        // Spawn at appropriate height: ground is at Y=0.05 (top of 0.1 thick floor)
-        // Capsule bottom should be at ground level, so center at 0.05 + 0.8 = 0.85
-        Transform::from_xyz(0.0, 0.85, 0.0),
+        Transform::from_xyz(0.0, ground + 0.85 + 0.1, 0.0),
         InheritedVisibility::default(),
         MassPropertiesBundle::default(),
         RigidBody::Dynamic,
+        // Physics runs at a fixed rate, smooth the position out in between. Only the translation,
+        // the facing is turned every frame already.
+        TranslationInterpolation,
+        // Raised while focusing, see `apply_controls`
+        GravityScale(1.0),
         Friction::new(0.1),
         //Collider::cuboid(0.1, 0.1, 0.1),
         TnuaController::default(),
@@ -67,6 +102,7 @@ pub fn on_player_spawn(on: On<Add, PlayerRoot>, mut commands: Commands, assets:
         RayCaster::new(Vec3::new(0.0, 0.0, 0.05), Dir3::NEG_Y),
         ControllerSensors::default(),
         ControllerState::Idle,
+        WeaponDrawn(true),
         //LockedAxes::ROTATION_LOCKED,
         children![(
             SceneRoot(assets.player.clone()),
@@ -80,14 +116,17 @@ pub struct PickupParticleEffect {
     pub spawn_time: f32,
 }
 
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
 pub fn pickup_stuff(
     mut commands: Commands,
     players: Query<Entity, With<PlayerRoot>>,
     children: Query<&Children>,
     colliders: Query<(&CollidingEntities, &Transform)>,
-    pickups: Query<(Entity, &Transform), With<Pickupable>>,
+    pickups: Query<(Entity, &Transform, &Pickupable), Without<Held>>,
+    mut collected: MessageWriter<ItemCollected>,
     assets: Res<GameAssets>,
     time: Res<Time>,
+    mut seeds: ParticleSeeds,
 ) {
     for player in players.iter() {
         let mut seen: HashSet<Entity> = HashSet::new();
@@ -96,12 +135,12 @@ pub fn pickup_stuff(
             .filter_map(|e| colliders.get(e).ok())
         {
             for other in colliding_entities.iter() {
-                if let Ok((picked_up, picked_up_transform)) = pickups.get(*other) {
+                if let Ok((picked_up, picked_up_transform, pickupable)) = pickups.get(*other) {
                     // Spawn golden particle effect relative to player position
                     commands.spawn((
                         ParticleEffect {
                             handle: assets.golden_pickup.clone(),
-                            prng_seed: Some(time.elapsed().as_micros() as u32),
+                            prng_seed: Some(seeds.next()),
                         },
                         Transform::from_translation(picked_up_transform.translation),
                         PickupParticleEffect {
@@ -110,9 +149,9 @@ pub fn pickup_stuff(
                     ));
 
                     // Despawn the picked up item
-                    if !seen.contains(&picked_up) {
+                    if seen.insert(picked_up) {
                         commands.entity(picked_up).despawn();
-                        seen.insert(picked_up);
+                        collected.write(ItemCollected(pickupable.0));
                     }
                 }
             }
@@ -175,12 +214,16 @@ pub fn add_mixamo_colliders(
             ));
         }
 
+        if name.as_str() == "mixamorigSpine" {
+            commands.entity(entity).insert(WeaponSocket::Back);
+        }
+
         if name.as_str() == "mixamorigRightHand" {
+            commands.entity(entity).insert(WeaponSocket::Hand);
             commands.entity(entity).with_child((
+                Sword,
                 SceneRoot(assets.sword.clone()),
-                Transform::from_translation(Vec3::new(88.3, 26.9, 0.0))
-                    .with_scale(Vec3::splat(40.0))
-                    .with_rotation(Quat::from_rotation_z(8.0)),
+                sword_transform(WeaponSocket::Hand),
                 Name::new("Sword"),
             ));
         }
@@ -244,20 +287,120 @@ pub fn controller_update_sensors(
     }
 }
 
+/// How far in front of the player a wall can be to grab its ledge
+const MANTLE_REACH: f32 = 0.6;
+/// Only ledges between these heights relative to the player's center can be mantled
+const MANTLE_MIN_HEIGHT: f32 = -0.2;
+const MANTLE_MAX_HEIGHT: f32 = 1.2;
+const MANTLE_DURATION: f32 = 0.6;
+
+pub const ROLL_KEY: KeyCode = KeyCode::KeyC;
+const ROLL_DURATION: f32 = 0.5;
+const ROLL_SPEED: f32 = 7.0;
+
+/// Look for a ledge to climb onto: a wall right in front of the player that ends within
+/// reach, with a surface on top. Returns where the player ends up standing on it.
+fn find_ledge(spatial_query: &SpatialQuery, position: Vec3, facing: Vec3) -> Option<Vec3> {
+    let facing = Dir3::new(facing.with_y(0.0)).ok()?;
+    // Small props are nothing to climb onto
+    let filter = SpatialQueryFilter::from_mask(view_blockers());
+
+    // Something to hold on to in front of the chest
+    spatial_query.cast_ray(position, facing, MANTLE_REACH, true, &filter)?;
+
+    // The wall must end below the highest reach, otherwise there is no lip to grab
+    let above = position + Vec3::Y * (MANTLE_MAX_HEIGHT + 0.1);
+    if spatial_query
+        .cast_ray(above, facing, MANTLE_REACH + 0.3, true, &filter)
+        .is_some()
+    {
+        return None;
+    }
+
+    // Find the top of the ledge, a bit past the edge so the player doesn't end up on the lip
+    let probe = above + facing * (MANTLE_REACH + 0.3);
+    let hit = spatial_query.cast_ray(
+        probe,
+        Dir3::NEG_Y,
+        MANTLE_MAX_HEIGHT - MANTLE_MIN_HEIGHT + 0.1,
+        true,
+        &filter,
+    )?;
+
+    // A hit right at the start means the probe is inside something, so there is no room
+    if hit.distance <= 0.0 {
+        return None;
+    }
+
+    // Stand on top at the usual float height
+    Some(probe - Vec3::Y * hit.distance + Vec3::Y * 0.85)
+}
+
+#[allow(clippy::type_complexity)]
 pub fn update_controller_state(
-    mut q: Query<(&mut ControllerState, &ControllerSensors, Forces)>,
+    mut q: Query<(
+        &mut ControllerState,
+        &ControllerSensors,
+        &mut Transform,
+        Forces,
+    )>,
     caster_and_hit: Single<(&RayCaster, &RayHits), With<FootRayCaster>>,
     keyboard: Res<ButtonInput<KeyCode>>,
-    time: Res<Time>,
+    time: Res<Time<Virtual>>,
+    spatial_query: SpatialQuery,
+    free_camera: Res<FreeCamera>,
+    console: Option<Res<Console>>,
 ) {
+    // Keep the states ticking while flying the free camera or typing into the console, just
+    // without any keys pressed
+    let no_keys = ButtonInput::default();
+    let keyboard = if free_camera.active || !console_closed(console) {
+        &no_keys
+    } else {
+        &*keyboard
+    };
+    // The states last as long in real time while focusing, like the movement itself
+    let delta = time.delta().mul_f32(real_time_compensation(&time));
     let jump_action = TnuaBuiltinJump {
         height: 2.5,
         fall_extra_gravity: 7.5,
         ..default()
     };
 
-    for (mut state, sensors, mut forces) in q.iter_mut() {
+    for (mut state, sensors, mut transform, mut forces) in q.iter_mut() {
         use ControllerState::*;
+
+        // Grab ledges while in the air and pushing forward
+        let ledge = |transform: &Transform| {
+            if !keyboard.pressed(KeyCode::KeyW) {
+                return None;
+            }
+            let end = find_ledge(
+                &spatial_query,
+                transform.translation,
+                sensors.facing_direction,
+            )?;
+            Some(Mantling(
+                Timer::from_seconds(MANTLE_DURATION, TimerMode::Once),
+                transform.translation,
+                end,
+            ))
+        };
+
+        // Roll the way the player is running, or straight ahead from standing still
+        let roll = || {
+            let running = sensors.running_velocity.with_y(0.0);
+            let direction = if running.length() > 0.1 {
+                running.normalize()
+            } else {
+                sensors.facing_direction.with_y(0.0).normalize_or_zero()
+            };
+            Rolling(
+                Timer::from_seconds(ROLL_DURATION, TimerMode::Once),
+                direction,
+            )
+        };
+
         match state.deref_mut() {
             Moving => {
                 if !sensors.standing_on_ground {
@@ -281,6 +424,10 @@ pub fn update_controller_state(
                 if keyboard.just_pressed(KeyCode::KeyV) {
                     *state = Attacking(Timer::from_seconds(0.9, TimerMode::Once));
                 }
+
+                if keyboard.just_pressed(ROLL_KEY) {
+                    *state = roll();
+                }
             }
             Idle => {
                 if sensors.actual_velocity.xz().length() > 0.1 {
@@ -305,6 +452,10 @@ pub fn update_controller_state(
                 if keyboard.just_pressed(KeyCode::KeyV) {
                     *state = Attacking(Timer::from_seconds(0.9, TimerMode::Once));
                 }
+
+                if keyboard.just_pressed(ROLL_KEY) {
+                    *state = roll();
+                }
             }
             Jumping(_) => {
                 match sensors.jump_state {
@@ -323,11 +474,13 @@ pub fn update_controller_state(
             Falling => {
                 if sensors.standing_on_ground {
                     *state = Idle;
+                } else if let Some(mantling) = ledge(&transform) {
+                    *state = mantling;
                 }
             }
             DropKicking(time_to_force, time_to_complete) => {
-                time_to_force.tick(time.delta());
-                time_to_complete.tick(time.delta());
+                time_to_force.tick(delta);
+                time_to_complete.tick(delta);
 
                 if time_to_force.just_finished() && !caster_and_hit.1.is_empty() {
                     dbg!(-caster_and_hit.0.global_direction());
@@ -339,22 +492,75 @@ pub fn update_controller_state(
                 }
             }
             Attacking(timer) => {
-                timer.tick(time.delta());
+                timer.tick(delta);
 
                 if timer.just_finished() {
                     *state = Idle;
                 }
             }
+            Mantling(timer, start, end) => {
+                timer.tick(delta);
+
+                // Pull up first, then move over the edge
+                let t = timer.fraction();
+                let up = (t / 0.6).min(1.0);
+                let over = ((t - 0.6) / 0.4).max(0.0);
+                let lifted = start.with_y(start.y.lerp(end.y, up));
+                transform.translation = lifted.lerp(*end, over);
+                *forces.linear_velocity_mut() = Vec3::ZERO;
+
+                if timer.is_finished() {
+                    *state = Idle;
+                }
+            }
+            Rolling(timer, _) => {
+                // Also keeps the distance the same, the roll speed is compensated as well
+                timer.tick(delta);
+
+                if timer.is_finished() {
+                    *state = Idle;
+                }
+            }
         };
     }
 }
 
+/// How fast the player runs around
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct MovementSettings {
+    /// Speed in meters per second without holding sprint
+    pub walk_speed: f32,
+    /// Speed in meters per second while holding sprint
+    pub sprint_speed: f32,
+}
+
+impl Default for MovementSettings {
+    fn default() -> Self {
+        Self {
+            walk_speed: 2.7,
+            sprint_speed: 5.4,
+        }
+    }
+}
+
 pub fn apply_controls(
     keyboard: Res<ButtonInput<KeyCode>>,
-    mut controller_query: Query<(&mut TnuaController, &ControllerState)>,
+    mut controller_query: Query<(&mut TnuaController, &ControllerState, &mut GravityScale)>,
     camera: Single<&Transform, With<Camera>>,
+    virtual_time: Res<Time<Virtual>>,
+    movement: Res<MovementSettings>,
+    free_camera: Res<FreeCamera>,
+    console: Option<Res<Console>>,
 ) {
-    let Ok((mut controller, state)) = controller_query.single_mut() else {
+    // The basis still has to be fed while flying the free camera or typing into the console
+    let no_keys = ButtonInput::default();
+    let keyboard = if free_camera.active || !console_closed(console) {
+        &no_keys
+    } else {
+        &*keyboard
+    };
+    let Ok((mut controller, state, mut gravity_scale)) = controller_query.single_mut() else {
         return;
     };
 
@@ -362,12 +568,10 @@ pub fn apply_controls(
     let forward = Vec3::new(forward.x, 0.0, forward.y);
     let sideways = (camera.rotation * Vec3::NEG_X).xz().normalize_or_zero();
     let sideways = Vec3::new(sideways.x, 0.0, sideways.y);
-    const SPEED: f32 = 2.7;
-
-    let sprint_factor = if keyboard.pressed(KeyCode::ShiftLeft) {
-        2.0
+    let speed = if keyboard.pressed(KeyCode::ShiftLeft) {
+        movement.sprint_speed
     } else {
-        1.0
+        movement.walk_speed
     };
 
     let mut direction = Vec3::ZERO;
@@ -394,47 +598,134 @@ pub fn apply_controls(
         direction = Vec3::ZERO;
     }
 
+    // Move at the normal pace while the world is slowed down by focus. Reaching that speed in
+    // the same real time needs the acceleration scaled twice, which goes for gravity too.
+    let compensation = real_time_compensation(&virtual_time);
+    let acceleration_compensation = compensation * compensation;
+    gravity_scale.set_if_neq(GravityScale(acceleration_compensation));
+
+    // A roll moves on its own, fast and snappy, no matter what is pressed
+    let (desired_velocity, acceleration) = match state {
+        ControllerState::Rolling(_, roll_direction) => (*roll_direction * ROLL_SPEED, 60.0),
+        _ => (direction.normalize_or_zero() * speed, 20.0),
+    };
+
     // Feed the basis every frame. Even if the player doesn't move - just use `desired_velocity:
     // Vec3::ZERO`. `TnuaController` starts without a basis, which will make the character collider
     // just fall.
     controller.basis(TnuaBuiltinWalk {
         // The `desired_velocity` determines how the character will move.
-        desired_velocity: direction.normalize_or_zero() * SPEED * sprint_factor,
+        desired_velocity: desired_velocity * compensation,
         // The `float_height` must be greater (even if by little) from the distance between the
         // character's center and the lowest point of its collider.
         float_height: 0.85,
         max_slope: PI / 3.0,
-        acceleration: 20.0,
-        spring_strength: 700.0,
+        acceleration: acceleration * acceleration_compensation,
+        // Holds the player up against the stronger gravity
+        spring_strength: 700.0 * acceleration_compensation,
         ..Default::default()
     });
 
     if let ControllerState::Jumping(jump) = state
         && keyboard.pressed(KeyCode::Space)
     {
-        controller.action(jump.clone());
+        // Same height, reached as fast as without focus under the scaled gravity
+        controller.action(TnuaBuiltinJump {
+            takeoff_above_velocity: jump.takeoff_above_velocity * compensation,
+            takeoff_extra_gravity: jump.takeoff_extra_gravity * acceleration_compensation,
+            fall_extra_gravity: jump.fall_extra_gravity * acceleration_compensation,
+            shorten_extra_gravity: jump.shorten_extra_gravity * acceleration_compensation,
+            peak_prevention_extra_gravity: jump.peak_prevention_extra_gravity
+                * acceleration_compensation,
+            ..jump.clone()
+        });
     }
 }
 
-/// Rotates the character to always face away from the camera (like Elden Ring)
+/// What the character faces while it is running
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Reflect)]
+pub enum CharacterFacing {
+    /// Turn towards the direction of movement
+    #[default]
+    Movement,
+    /// Keep facing away from the camera and strafe sideways
+    Camera,
+}
+
+/// How fast and when the character turns
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct CharacterTurnSettings {
+    pub facing: CharacterFacing,
+    /// Turn speed in radians per second, while moving and while idle
+    pub turn_speed: f32,
+    /// When idle, only start turning towards the camera direction once it is off by more than
+    /// this many radians, so small camera jitters don't make the character shuffle around
+    pub idle_deadzone: f32,
+}
+
+impl Default for CharacterTurnSettings {
+    fn default() -> Self {
+        Self {
+            facing: CharacterFacing::default(),
+            turn_speed: 4.0,
+            idle_deadzone: 0.35,
+        }
+    }
+}
+
+/// Rotates the character towards where it is running (or away from the camera, depending on
+/// [`CharacterFacing`]), and to face away from the camera when it is standing still (like
+/// Elden Ring)
 pub fn rotate_character_to_movement(
-    mut query: Query<(&mut Transform, &mut ControllerSensors), With<TnuaController>>,
-    time: Res<Time>,
+    mut query: Query<
+        (&mut Transform, &mut ControllerSensors, &ControllerState),
+        With<TnuaController>,
+    >,
+    camera: Query<&ThirdPersonCamera>,
+    settings: Res<CharacterTurnSettings>,
+    // Real time, so turning doesn't slow down while focusing
+    time: Res<Time<Real>>,
+    mut idle_turning: Local<bool>,
 ) {
-    for (mut transform, sensors) in query.iter_mut() {
-        if sensors.running_velocity.length() > 0.1 {
-            let target_rotation = Quat::from_rotation_y(
+    for (mut transform, sensors, state) in query.iter_mut() {
+        let running = sensors.running_velocity.length() > 0.1;
+        let target_rotation = if running && settings.facing == CharacterFacing::Movement {
+            *idle_turning = false;
+            Quat::from_rotation_y(
                 PI - sensors
                     .running_velocity
                     .x
                     .atan2(-sensors.running_velocity.z),
-            );
+            )
+        } else if let Ok(camera) = camera.single()
+            && (running || matches!(state, ControllerState::Idle))
+        {
+            // The camera orbits at `yaw`, so facing away from it is half a turn further
+            let target_rotation = Quat::from_rotation_y(camera.yaw + PI);
+            if running {
+                *idle_turning = false;
+            } else {
+                let angle = transform.rotation.angle_between(target_rotation);
+                if angle > settings.idle_deadzone {
+                    *idle_turning = true;
+                } else if angle < 0.01 {
+                    *idle_turning = false;
+                }
 
-            // Smoothly rotate character to match target
-            const ROTATION_SPEED: f32 = 4.0; // radians per second
-            transform.rotation = transform
-                .rotation
-                .slerp(target_rotation, ROTATION_SPEED * time.delta_secs());
-        }
+                if !*idle_turning {
+                    continue;
+                }
+            }
+            target_rotation
+        } else {
+            continue;
+        };
+
+        // Smoothly rotate character to match target
+        transform.rotation = transform.rotation.slerp(
+            target_rotation,
+            (settings.turn_speed * time.delta_secs()).min(1.0),
+        );
     }
 }
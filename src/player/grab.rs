@@ -0,0 +1,118 @@
+use avian3d::prelude::*;
+use bevy::prelude::*;
+
+use crate::player::controller::{PlayerRoot, all_except_player};
+
+/// Marks a prop that is currently being carried by the player
+#[derive(Component)]
+pub struct Held;
+
+/// Attached to the player while it is carrying a prop
+#[derive(Component)]
+pub struct Grabbing(pub Entity);
+
+pub const GRAB_KEY: KeyCode = KeyCode::KeyE;
+/// How far in front of the player we look for something to grab
+const GRAB_RANGE: f32 = 1.5;
+/// Distance in front of the player (along the camera direction) where the prop is held
+const HOLD_DISTANCE: f32 = 1.2;
+const HOLD_HEIGHT: f32 = 1.2;
+/// Let go automatically when the prop gets stuck this far away from the hold point
+const BREAK_DISTANCE: f32 = 3.0;
+const SPRING_STIFFNESS: f32 = 120.0;
+const SPRING_DAMPING: f32 = 18.0;
+const THROW_IMPULSE: f32 = 2.0;
+
+fn hold_point(player: &Transform, camera: &Transform) -> Vec3 {
+    player.translation + Vec3::Y * HOLD_HEIGHT + camera.forward() * HOLD_DISTANCE
+}
+
+/// Grab the nearest dynamic body in front of the player when the grab key is pressed
+#[allow(clippy::type_complexity)]
+pub fn grab_nearest_prop(
+    mut commands: Commands,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    player: Single<(Entity, &Transform), (With<PlayerRoot>, Without<Grabbing>)>,
+    camera: Single<&Transform, With<Camera>>,
+    spatial_query: SpatialQuery,
+    bodies: Query<(&RigidBody, &GlobalTransform)>,
+) {
+    if !keyboard.just_pressed(GRAB_KEY) {
+        return;
+    }
+
+    let (player, player_transform) = *player;
+    let forward = camera.forward().with_y(0.0).normalize_or_zero();
+    let center = player_transform.translation + Vec3::Y * HOLD_HEIGHT * 0.5 + forward;
+
+    let nearest = spatial_query
+        .shape_intersections(
+            &Collider::sphere(GRAB_RANGE),
+            center,
+            Quat::IDENTITY,
+            &SpatialQueryFilter::from_mask(all_except_player()),
+        )
+        .into_iter()
+        .filter_map(|e| {
+            let (body, gt) = bodies.get(e).ok()?;
+            let towards = gt.translation() - player_transform.translation;
+            (body.is_dynamic() && towards.dot(forward) > 0.0).then_some((e, towards.length()))
+        })
+        .min_by(|(_, a), (_, b)| a.total_cmp(b));
+
+    if let Some((prop, _)) = nearest {
+        commands.entity(prop).insert(Held);
+        commands.entity(player).insert(Grabbing(prop));
+    }
+}
+
+/// Throw the carried prop in the camera direction once the grab key is released
+pub fn release_grabbed_prop(
+    mut commands: Commands,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    player: Single<(Entity, &Grabbing)>,
+    camera: Single<&Transform, With<Camera>>,
+    mut props: Query<Forces, With<Held>>,
+) {
+    let (player, Grabbing(prop)) = *player;
+
+    let Ok(mut forces) = props.get_mut(*prop) else {
+        // the prop got despawned while we were holding it
+        commands.entity(player).remove::<Grabbing>();
+        return;
+    };
+
+    if keyboard.just_released(GRAB_KEY) {
+        forces.apply_linear_impulse(camera.forward() * THROW_IMPULSE);
+        commands.entity(*prop).remove::<Held>();
+        commands.entity(player).remove::<Grabbing>();
+    }
+}
+
+/// Pull the carried prop towards the hold point with a damped spring
+pub fn hold_grabbed_prop(
+    mut commands: Commands,
+    player: Single<(Entity, &Transform, &LinearVelocity, &Grabbing)>,
+    camera: Single<&Transform, With<Camera>>,
+    mut props: Query<(Forces, &GlobalTransform), With<Held>>,
+    gravity: Res<Gravity>,
+) {
+    let (player, player_transform, player_velocity, Grabbing(prop)) = *player;
+    let Ok((mut forces, gt)) = props.get_mut(*prop) else {
+        return;
+    };
+
+    let towards = hold_point(player_transform, &camera) - gt.translation();
+    if towards.length() > BREAK_DISTANCE {
+        commands.entity(*prop).remove::<Held>();
+        commands.entity(player).remove::<Grabbing>();
+        return;
+    }
+
+    let relative_velocity = forces.linear_velocity() - player_velocity.0;
+    forces.apply_linear_acceleration(
+        SPRING_STIFFNESS * towards - SPRING_DAMPING * relative_velocity - gravity.0,
+    );
+    // Keep it from spinning wildly in our hands
+    *forces.angular_velocity_mut() *= 0.9;
+}
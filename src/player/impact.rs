@@ -0,0 +1,128 @@
+use avian3d::prelude::*;
+use bevy::prelude::*;
+use bevy_hanabi::prelude::*;
+
+use crate::assets::{GameAssets, IMPACT_STRENGTH_PROPERTY, ParticleSeeds};
+use crate::game::Surface;
+use crate::player::controller::{ControllerSensors, ControllerState, FootRayCaster, PlayerRoot};
+
+/// Landing slower than this doesn't kick up any dust
+const MIN_LANDING_SPEED: f32 = 3.0;
+/// Impact speed at which the burst has its default size
+const REFERENCE_IMPACT_SPEED: f32 = 8.0;
+/// The drop kick is a hard hit regardless of how fast the player moves
+const DROP_KICK_IMPACT_SPEED: f32 = 12.0;
+
+#[derive(Component)]
+pub struct ImpactParticleEffect {
+    pub spawn_time: f32,
+}
+
+fn spawn_impact_effect(
+    commands: &mut Commands,
+    assets: &GameAssets,
+    time: &Time,
+    seed: u32,
+    surface: Surface,
+    point: Vec3,
+    speed: f32,
+) {
+    let handle = match surface {
+        Surface::Grass => assets.grass_impact.clone(),
+        Surface::Stone => assets.stone_impact.clone(),
+    };
+    let strength = (speed / REFERENCE_IMPACT_SPEED).clamp(0.3, 2.0);
+
+    commands.spawn((
+        ParticleEffect {
+            handle,
+            prng_seed: Some(seed),
+        },
+        EffectProperties::default()
+            .with_properties([(IMPACT_STRENGTH_PROPERTY.to_string(), strength.into())]),
+        Transform::from_translation(point),
+        ImpactParticleEffect {
+            spawn_time: time.elapsed_secs(),
+        },
+    ));
+}
+
+/// Kick up dust matching the ground when the player lands or the drop kick hits
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
+pub fn spawn_impact_particles(
+    mut commands: Commands,
+    player: Single<
+        (
+            &ControllerState,
+            &ControllerSensors,
+            &GlobalTransform,
+            &RayHits,
+        ),
+        With<PlayerRoot>,
+    >,
+    foot: Single<(&RayCaster, &RayHits), With<FootRayCaster>>,
+    surfaces: Query<&Surface>,
+    assets: Res<GameAssets>,
+    time: Res<Time>,
+    mut seeds: ParticleSeeds,
+    mut fall_speed: Local<f32>,
+) {
+    let (state, sensors, gt, hits) = *player;
+
+    if let ControllerState::Falling = state {
+        *fall_speed = fall_speed.max(-sensors.actual_velocity.y);
+    } else if *fall_speed > 0.0 {
+        let speed = std::mem::take(&mut *fall_speed);
+        // The ray also passes through the player's own limbs, those have no surface
+        let ground = hits
+            .iter_sorted()
+            .find_map(|hit| Some((*surfaces.get(hit.entity).ok()?, hit.distance)));
+
+        if speed > MIN_LANDING_SPEED
+            && let Some((surface, distance)) = ground
+        {
+            let point = gt.translation() - Vec3::Y * distance;
+            spawn_impact_effect(
+                &mut commands,
+                &assets,
+                &time,
+                seeds.next(),
+                surface,
+                point,
+                speed,
+            );
+        }
+    }
+
+    let (caster, foot_hits) = *foot;
+    if let ControllerState::DropKicking(time_to_force, _) = state
+        && time_to_force.just_finished()
+        && let Some(hit) = foot_hits.iter_sorted().next()
+    {
+        let point = caster.global_origin() + caster.global_direction() * hit.distance;
+        let surface = surfaces.get(hit.entity).copied().unwrap_or_default();
+        spawn_impact_effect(
+            &mut commands,
+            &assets,
+            &time,
+            seeds.next(),
+            surface,
+            point,
+            DROP_KICK_IMPACT_SPEED,
+        );
+    }
+}
+
+pub fn cleanup_impact_particles(
+    mut commands: Commands,
+    query: Query<(Entity, &ImpactParticleEffect)>,
+    time: Res<Time>,
+) {
+    const DURATION: f32 = 1.5; // The longest lived dust is gone by then
+
+    for (entity, effect) in query.iter() {
+        if time.elapsed_secs() - effect.spawn_time > DURATION {
+            commands.entity(entity).despawn();
+        }
+    }
+}
@@ -0,0 +1,77 @@
+use avian3d::prelude::*;
+use bevy::prelude::*;
+use bevy_hanabi::prelude::*;
+
+use crate::assets::{GameAssets, IMPACT_STRENGTH_PROPERTY, ParticleSeeds};
+use crate::focus::real_time_compensation;
+use crate::game::Fragile;
+use crate::player::controller::{ControllerSensors, ControllerState, all_except_player};
+use crate::player::impact::ImpactParticleEffect;
+
+/// Time into the slash at which the sword hits
+const SWING_HIT_TIME: f32 = 0.35;
+/// The area in front of the player the swing sweeps through
+const SWING_REACH: f32 = 0.8;
+const SWING_RADIUS: f32 = 0.9;
+const SWING_HEIGHT: f32 = 0.3;
+const SWING_IMPULSE: f32 = 1.5;
+
+/// Knock props around with the sword, and shatter the fragile ones
+#[allow(clippy::type_complexity)]
+pub fn sword_hits_props(
+    mut commands: Commands,
+    player: Query<(&ControllerState, &ControllerSensors, &Transform)>,
+    mut props: Query<(Forces, &GlobalTransform, Has<Fragile>)>,
+    spatial_query: SpatialQuery,
+    assets: Res<GameAssets>,
+    time: Res<Time<Virtual>>,
+    mut seeds: ParticleSeeds,
+) {
+    for (state, sensors, transform) in player.iter() {
+        let ControllerState::Attacking(timer) = state else {
+            continue;
+        };
+
+        // Only on the frame the swing passes the hit time, the timer runs in real time
+        let elapsed = timer.elapsed_secs();
+        let delta = time.delta_secs() * real_time_compensation(&time);
+        if elapsed < SWING_HIT_TIME || elapsed - delta >= SWING_HIT_TIME {
+            continue;
+        }
+
+        let center =
+            transform.translation + sensors.facing_direction * SWING_REACH + Vec3::Y * SWING_HEIGHT;
+
+        for entity in spatial_query.shape_intersections(
+            &Collider::sphere(SWING_RADIUS),
+            center,
+            Quat::IDENTITY,
+            &SpatialQueryFilter::from_mask(all_except_player()),
+        ) {
+            let Ok((mut forces, gt, fragile)) = props.get_mut(entity) else {
+                continue;
+            };
+
+            if fragile {
+                commands.entity(entity).despawn();
+                commands.spawn((
+                    ParticleEffect {
+                        handle: assets.glass_shatter.clone(),
+                        prng_seed: Some(seeds.next()),
+                    },
+                    EffectProperties::default()
+                        .with_properties([(IMPACT_STRENGTH_PROPERTY.to_string(), 1.0_f32.into())]),
+                    Transform::from_translation(gt.translation()),
+                    ImpactParticleEffect {
+                        spawn_time: time.elapsed_secs(),
+                    },
+                ));
+            } else {
+                let away = (gt.translation() - transform.translation)
+                    .with_y(0.0)
+                    .normalize_or_zero();
+                forces.apply_linear_impulse((away + Vec3::Y * 0.5) * SWING_IMPULSE);
+            }
+        }
+    }
+}
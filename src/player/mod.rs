@@ -2,17 +2,31 @@ use bevy::prelude::*;
 
 use crate::animations_utils::LinkAnimationPlayerPluginFor;
 use crate::assets::MyStates;
+use crate::camera::free_camera_inactive;
+use crate::console::console_closed;
 use crate::player::animations::*;
 use crate::player::controller::*;
+use crate::player::grab::*;
+use crate::player::impact::*;
+use crate::player::melee::*;
+use crate::player::weapon::*;
 
 pub mod animations;
 pub mod controller;
+pub mod grab;
+pub mod impact;
+pub mod melee;
+pub mod weapon;
 
 pub struct PlayerPlugin;
 
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugins(LinkAnimationPlayerPluginFor::<PlayerRoot>::default());
+        app.register_type::<CharacterTurnSettings>();
+        app.init_resource::<CharacterTurnSettings>();
+        app.register_type::<MovementSettings>();
+        app.init_resource::<MovementSettings>();
         app.add_observer(on_player_spawn);
         app.add_observer(on_animation_player_loaded);
         app.add_systems(
@@ -25,6 +39,8 @@ impl Plugin for PlayerPlugin {
             (
                 controller_update_sensors,
                 update_controller_state,
+                spawn_impact_particles,
+                sword_hits_props,
                 pickup_stuff,
                 apply_controls,
                 animations_from_controller,
@@ -35,7 +51,23 @@ impl Plugin for PlayerPlugin {
         );
         app.add_systems(
             Update,
-            cleanup_pickup_particles.run_if(in_state(MyStates::Next)),
+            (cleanup_pickup_particles, cleanup_impact_particles).run_if(in_state(MyStates::Next)),
+        );
+        app.add_systems(
+            Update,
+            (grab_nearest_prop, release_grabbed_prop).chain().run_if(
+                in_state(MyStates::Next)
+                    .and(free_camera_inactive)
+                    .and(console_closed),
+            ),
+        );
+        app.add_systems(
+            Update,
+            draw_and_sheathe_sword.run_if(in_state(MyStates::Next)),
+        );
+        app.add_systems(
+            FixedUpdate,
+            hold_grabbed_prop.run_if(in_state(MyStates::Next)),
         );
     }
 }
@@ -0,0 +1,71 @@
+use bevy::prelude::*;
+
+use crate::player::controller::{ControllerState, PlayerRoot};
+
+/// Whether the player has the sword in hand or on their back
+#[derive(Component)]
+pub struct WeaponDrawn(pub bool);
+
+/// Marks the sword model
+#[derive(Component)]
+pub struct Sword;
+
+/// Bones the sword can be attached to
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+pub enum WeaponSocket {
+    Hand,
+    Back,
+}
+
+/// Put the sword away after not attacking for this long
+const SHEATHE_DELAY: f32 = 4.0;
+
+/// Placement of the sword relative to the bone of the given socket
+pub fn sword_transform(socket: WeaponSocket) -> Transform {
+    match socket {
+        WeaponSocket::Hand => Transform::from_translation(Vec3::new(88.3, 26.9, 0.0))
+            .with_scale(Vec3::splat(40.0))
+            .with_rotation(Quat::from_rotation_z(8.0)),
+        // Diagonally across the back with the handle over the right shoulder
+        WeaponSocket::Back => Transform::from_translation(Vec3::new(0.0, 25.0, -20.0))
+            .with_scale(Vec3::splat(40.0))
+            .with_rotation(Quat::from_rotation_z(-2.4)),
+    }
+}
+
+/// Draw the sword when attacking, and sheathe it again once out of combat for a while
+pub fn draw_and_sheathe_sword(
+    mut commands: Commands,
+    mut player: Single<(&ControllerState, &mut WeaponDrawn), With<PlayerRoot>>,
+    sword: Single<Entity, With<Sword>>,
+    sockets: Query<(Entity, &WeaponSocket)>,
+    time: Res<Time>,
+    mut since_attack: Local<f32>,
+) {
+    let (state, drawn) = &mut *player;
+
+    if matches!(state, ControllerState::Attacking(_)) {
+        *since_attack = 0.0;
+    } else {
+        *since_attack += time.delta_secs();
+    }
+
+    let should_be_drawn = *since_attack < SHEATHE_DELAY;
+    if drawn.0 == should_be_drawn {
+        return;
+    }
+
+    let socket = if should_be_drawn {
+        WeaponSocket::Hand
+    } else {
+        WeaponSocket::Back
+    };
+    let Some((bone, _)) = sockets.iter().find(|(_, s)| **s == socket) else {
+        return;
+    };
+
+    commands
+        .entity(*sword)
+        .insert((ChildOf(bone), sword_transform(socket)));
+    drawn.0 = should_be_drawn;
+}
@@ -3,6 +3,7 @@ use bevy::{math::Affine2, prelude::*};
 use bevy_hanabi::prelude::*;
 
 use crate::assets::GameAssets;
+use crate::game::{GraphicsQuality, Surface};
 
 #[derive(Component)]
 #[require(Transform, InheritedVisibility)]
@@ -28,8 +29,10 @@ fn on_spawn_torch(
     assets: Res<GameAssets>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
+    quality: Res<GraphicsQuality>,
 ) {
     let root = on.event_target();
+    commands.entity(root).insert(Name::new("Torch"));
 
     // cube with stone texture
     let cube = commands
@@ -44,6 +47,7 @@ fn on_spawn_torch(
             ChildOf(root),
             RigidBody::Static,
             Collider::cuboid(1.0, 3.0, 1.0),
+            Surface::Stone,
         ))
         .id();
 
@@ -66,10 +70,10 @@ fn on_spawn_torch(
         ))
         .id();
 
-    // light
+    // light, torches spawned later on (like in the dungeon) follow the current quality too
     commands.spawn((
         PointLight {
-            shadows_enabled: true,
+            shadows_enabled: quality.torch_shadows(),
             intensity: light_consts::lumens::LUMENS_PER_LED_WATTS * 150.0,
             color: Color::srgb(1.0, 0.6, 0.2),
             ..default()